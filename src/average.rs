@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+
+use crate::complex::Complex;
+
+/// Function averaged over the orbit for the average coloring modes.
+///
+/// Each term lies in [0, 1], so the averages do as well.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OrbitAverage {
+    /// Nothing is averaged, which keeps the kernels fast.
+    #[default]
+    None,
+
+    /// sin(density arg z) / 2 + 1/2, which draws stripes that follow the external angles.
+    Stripe { density: f64 },
+
+    /// Where |z| lies between the bounds | |z_prev|^2 - |c| | and |z_prev|^2 + |c| given
+    /// by the triangle inequality.
+    TriangleInequality,
+
+    /// Angle between successive steps of the orbit, divided by pi.
+    Curvature,
+}
+
+/// Sum of the terms of an orbit average up to the final iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AverageSum {
+    pub sum: f64,
+
+    /// The final term, so the average up to the previous iteration is known as well.
+    pub last: f64,
+    pub count: i32,
+}
+
+impl AverageSum {
+    /// Blends the averages up to the previous and the final iteration.
+    ///
+    /// With `fraction` taken from how far past the escape radius the orbit went, the
+    /// result is continuous across iteration bands.
+    pub fn interpolated(&self, fraction: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let last = self.sum / self.count as f64;
+        if self.count == 1 {
+            return last;
+        }
+        let previous = (self.sum - self.last) / (self.count - 1) as f64;
+        previous + (last - previous) * fraction
+    }
+}
+
+/// Adds up an orbit average as a kernel iterates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Averager {
+    average: OrbitAverage,
+    c_norm: f64,
+
+    // Iterate before the previous one, for the curvature.
+    before: Option<Complex<f64>>,
+    sum: AverageSum,
+}
+
+impl Averager {
+    pub fn new(average: OrbitAverage, c: Complex<f64>) -> Self {
+        Averager { average, c_norm: c.norm(), before: None, sum: AverageSum::default() }
+    }
+
+    /// Adds the term of one step of the orbit from `previous` to `z`.
+    pub fn add(&mut self, previous: Complex<f64>, z: Complex<f64>) {
+        let term = match self.average {
+            OrbitAverage::None => return,
+            OrbitAverage::Stripe { density } => 0.5 * (density * z.arg()).sin() + 0.5,
+            OrbitAverage::TriangleInequality => {
+                let previous2 = previous.norm_sqr();
+                let low = (previous2 - self.c_norm).abs();
+                (z.norm() - low) / (previous2 + self.c_norm - low)
+            },
+            OrbitAverage::Curvature => {
+                let before = self.before.replace(previous);
+                match before {
+                    Some(before) => ((z - previous) / (previous - before)).arg().abs() / PI,
+                    None => return,
+                }
+            },
+        };
+
+        // The first steps can divide zero by zero.
+        if term.is_finite() {
+            self.sum.sum += term;
+            self.sum.last = term;
+            self.sum.count += 1;
+        }
+    }
+
+    pub fn sum(&self) -> AverageSum {
+        self.sum
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn average(average: OrbitAverage, orbit: &[Complex<f64>], c: Complex<f64>) -> AverageSum {
+        let mut averager = Averager::new(average, c);
+        for pair in orbit.windows(2) {
+            averager.add(pair[0], pair[1]);
+        }
+        averager.sum()
+    }
+
+    #[test]
+    fn test_interpolated() {
+        let sum = AverageSum { sum: 3.0, last: 1.0, count: 4 };
+
+        assert_eq!(2.0 / 3.0, sum.interpolated(0.0));
+        assert_eq!(0.75, sum.interpolated(1.0));
+        assert_eq!(0.0, AverageSum::default().interpolated(0.5));
+    }
+
+    #[test]
+    fn test_terms() {
+        let c = Complex::new(1.0, 0.0);
+        let orbit = [Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(2.0, 0.0), Complex::new(5.0, 0.0)];
+
+        assert_eq!(AverageSum { sum: 1.5, last: 0.5, count: 3 }, average(OrbitAverage::Stripe { density: 3.0 }, &orbit, c));
+        // Adding a positive c to a positive z reaches the upper bound.
+        assert_eq!(AverageSum { sum: 2.0, last: 1.0, count: 2 }, average(OrbitAverage::TriangleInequality, &orbit, c));
+        assert_eq!(AverageSum { sum: 0.0, last: 0.0, count: 2 }, average(OrbitAverage::Curvature, &orbit, c));
+        assert_eq!(AverageSum::default(), average(OrbitAverage::None, &orbit, c));
+    }
+
+    #[test]
+    fn test_curvature_turn() {
+        let orbit = [Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(1.0, 1.0)];
+
+        assert_eq!(0.5, average(OrbitAverage::Curvature, &orbit, Complex::zero()).sum);
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
+
+use crate::singlecache::SingleCache;
+
+// Key, quality and cancel flag of a job.
+type Running<K> = (K, u32, Arc<AtomicBool>);
+
+// State shared between the worker and its jobs: the latest value of a key and its quality,
+// and the job in flight. Jobs check whether they were cancelled while holding the lock, so
+// a cancelled job can't publish over the values of the job replacing it.
+struct Shared<K, V> {
+    latest: SingleCache<K, (Arc<V>, u32)>,
+    running: Option<Running<K>>,
+}
+
+/// Computes values away from the UI thread, one job at a time, keeping the latest value
+/// published.
+///
+/// Jobs may publish values in passes of increasing quality, each shown as it finishes.
+/// Requesting a different key cancels the job in flight, so values that are no longer
+/// needed, such as those of viewports scrolled past, don't hold up the latest one.
+/// Where there are no threads, as on the web, jobs run on the calling thread instead.
+pub struct BackgroundWorker<K, V> {
+    shared: Arc<Mutex<Shared<K, V>>>,
+}
+
+/// What a job is given to check whether it was cancelled and to publish its values.
+pub struct Job<K, V> {
+    key: K,
+    cancel: Arc<AtomicBool>,
+    shared: Arc<Mutex<Shared<K, V>>>,
+    published: Box<dyn Fn() + Send>,
+}
+
+impl<K: PartialEq + Clone, V> Job<K, V> {
+    /// Returns the flag that is set once the job is cancelled.
+    pub fn cancel(&self) -> &AtomicBool {
+        &self.cancel
+    }
+
+    /// Keeps a value as the latest of the key unless the job was cancelled, returning it.
+    pub fn publish(&self, value: V, quality: u32) -> Arc<V> {
+        let value = Arc::new(value);
+        let mut shared = self.shared.lock().unwrap();
+        if !self.cancel.load(Ordering::Relaxed) {
+            shared.latest.set(self.key.clone(), (value.clone(), quality));
+            drop(shared);
+            (self.published)();
+        }
+        value
+    }
+
+    // Clears the job in flight if it is still this one, so its key can be asked for again.
+    fn finish(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        if matches!(&shared.running, Some((_, _, cancel)) if Arc::ptr_eq(cancel, &self.cancel)) {
+            shared.running = None;
+        }
+    }
+}
+
+impl<K, V> BackgroundWorker<K, V>
+where
+    K: PartialEq + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        BackgroundWorker { shared: Arc::new(Mutex::new(Shared { latest: SingleCache::new(), running: None })) }
+    }
+
+    /// Returns the latest value published for the key, which may be of a lower quality
+    /// than asked for. Starts a job to reach `quality` unless it was reached or the job in
+    /// flight will reach it.
+    ///
+    /// The job is given the latest value and its quality, to refine rather than start over.
+    /// `published` is called on the job's thread whenever it publishes a value.
+    pub fn get_or_start<J, N>(&mut self, key: K, quality: u32, job: J, published: N) -> Option<Arc<V>>
+    where
+        J: FnOnce(Option<(Arc<V>, u32)>, &Job<K, V>) + Send + 'static,
+        N: Fn() + Send + 'static,
+    {
+        let mut shared = self.shared.lock().unwrap();
+        let latest = shared.latest.get(&key);
+        let reached = matches!(&latest, Some((_, latest_quality)) if *latest_quality >= quality);
+        let running = matches!(&shared.running, Some((running_key, running_quality, _)) if *running_key == key && *running_quality >= quality);
+        if reached || running {
+            return latest.map(|(value, _)| value);
+        }
+
+        // Cancelled under the lock, so the job can't publish once the new one has started.
+        if let Some((_, _, cancel)) = shared.running.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        shared.running = Some((key.clone(), quality, cancel.clone()));
+        drop(shared);
+
+        let context = Job { key: key.clone(), cancel, shared: self.shared.clone(), published: Box::new(published) };
+        let task = move || {
+            job(latest, &context);
+            context.finish();
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(task);
+        #[cfg(target_arch = "wasm32")]
+        task();
+
+        // Jobs run on the calling thread have already published their values.
+        self.shared.lock().unwrap().latest.get(&key).map(|(value, _)| value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn test_publishes_in_background() {
+        let mut worker = BackgroundWorker::new();
+        let (sender, receiver) = mpsc::channel();
+
+        let job = |_, job: &Job<i32, i32>| { job.publish(10, 1); };
+        assert_eq!(None, worker.get_or_start(1, 1, job, move || sender.send(()).unwrap()));
+        receiver.recv().unwrap();
+
+        let value = worker.get_or_start(1, 1, |_, _| panic!("computed again"), || ());
+        assert_eq!(Some(Arc::new(10)), value);
+    }
+
+    #[test]
+    fn test_refines_latest() {
+        let mut worker = BackgroundWorker::new();
+        let (sender, receiver) = mpsc::channel();
+
+        let coarse = |_, job: &Job<i32, i32>| { job.publish(10, 1); };
+        worker.get_or_start(1, 1, coarse, move || sender.send(()).unwrap());
+        receiver.recv().unwrap();
+
+        // Asking for a higher quality refines what was published.
+        let (sender, receiver) = mpsc::channel();
+        let fine = |latest: Option<(Arc<i32>, u32)>, job: &Job<i32, i32>| {
+            let (value, quality) = latest.unwrap();
+            job.publish(*value + 1, quality + 1);
+        };
+        assert_eq!(Some(Arc::new(10)), worker.get_or_start(1, 2, fine, move || sender.send(()).unwrap()));
+        receiver.recv().unwrap();
+
+        assert_eq!(Some(Arc::new(11)), worker.get_or_start(1, 2, |_, _| (), || ()));
+    }
+
+    #[test]
+    fn test_new_key_cancels() {
+        let mut worker = BackgroundWorker::new();
+        let (started_sender, started) = mpsc::channel();
+        let (cancelled_sender, cancelled) = mpsc::channel();
+
+        worker.get_or_start(1, 1, move |_, job: &Job<i32, i32>| {
+            started_sender.send(()).unwrap();
+            while !job.cancel().load(Ordering::Relaxed) {
+                std::thread::yield_now();
+            }
+            job.publish(10, 1);
+            cancelled_sender.send(()).unwrap();
+        }, || panic!("cancelled job published"));
+        started.recv().unwrap();
+
+        let (done_sender, done) = mpsc::channel();
+        worker.get_or_start(2, 1, |_, job: &Job<i32, i32>| { job.publish(20, 1); }, move || done_sender.send(()).unwrap());
+        cancelled.recv().unwrap();
+        done.recv().unwrap();
+
+        assert_eq!(Some(Arc::new(20)), worker.get_or_start(2, 1, |_, _| (), || ()));
+    }
+
+    #[test]
+    fn test_cancelled_job_publishes_late() {
+        let mut worker = BackgroundWorker::new();
+        let (started_sender, started) = mpsc::channel();
+        let (replaced_sender, replaced) = mpsc::channel::<()>();
+        let (finished_sender, finished) = mpsc::channel();
+
+        worker.get_or_start(1, 1, move |_, job: &Job<i32, i32>| {
+            started_sender.send(()).unwrap();
+            replaced.recv().unwrap();
+            job.publish(10, 1);
+            finished_sender.send(()).unwrap();
+        }, || panic!("cancelled job published"));
+        started.recv().unwrap();
+
+        let (done_sender, done) = mpsc::channel();
+        worker.get_or_start(2, 1, |_, job: &Job<i32, i32>| { job.publish(20, 1); }, move || done_sender.send(()).unwrap());
+        done.recv().unwrap();
+
+        // The first job only publishes once its replacement has.
+        replaced_sender.send(()).unwrap();
+        finished.recv().unwrap();
+        assert_eq!(Some(Arc::new(20)), worker.get_or_start(2, 1, |_, _| panic!("computed again"), || ()));
+    }
+
+    #[test]
+    fn test_finished_job_restarts() {
+        let mut worker = BackgroundWorker::new();
+        let (sender, receiver) = mpsc::channel();
+
+        // A job that stops short of the quality asked for can be started again.
+        worker.get_or_start(1, 2, |_, job: &Job<i32, i32>| { job.publish(10, 1); }, move || sender.send(()).unwrap());
+        receiver.recv().unwrap();
+        while worker.shared.lock().unwrap().running.is_some() {
+            std::thread::yield_now();
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        worker.get_or_start(1, 2, |_, job: &Job<i32, i32>| { job.publish(11, 2); }, move || sender.send(()).unwrap());
+        receiver.recv().unwrap();
+        assert_eq!(Some(Arc::new(11)), worker.get_or_start(1, 2, |_, _| (), || ()));
+    }
+}
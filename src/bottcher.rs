@@ -0,0 +1,110 @@
+use std::f64::consts::TAU;
+
+use crate::complex::Complex;
+
+/// Number of texture tiles around the set in the outermost band. Each band in is split
+/// into twice as many, which keeps the tiles close to square as the bands narrow.
+pub const TEXTURE_TILES: f64 = 12.0;
+
+/// Builds the logarithm of the Böttcher coordinate as a kernel iterates.
+///
+/// The Böttcher coordinate is the limit of z_n^(1/2^n), which conjugates z^2 + c to
+/// z^2 outside the set. Its logarithm is ln z plus a series with one term for each step,
+/// ln(1 + c / z^2) / 2^(n+1), which converges quickly once z is large. The real part of the
+/// logarithm is the potential and the imaginary part the external angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BottcherTracker {
+    enabled: bool,
+    log: Option<Complex<f64>>,
+
+    // Weight of the next step's term.
+    weight: f64,
+}
+
+impl BottcherTracker {
+    pub fn new(enabled: bool) -> Self {
+        BottcherTracker { enabled, log: None, weight: 1.0 }
+    }
+
+    /// Adds the step of the orbit from `previous` to `z` = previous^2 + c.
+    ///
+    /// An orbit starting at 0, as the Mandelbrot set's does, starts the series at z_1 = c.
+    pub fn add(&mut self, previous: Complex<f64>, z: Complex<f64>, c: Complex<f64>) {
+        if !self.enabled {
+            return;
+        }
+        let log = match self.log {
+            Some(log) => log,
+            None if previous.norm_sqr() == 0.0 => {
+                self.log = Some(z.ln());
+                return;
+            },
+            None => previous.ln(),
+        };
+
+        let term = (Complex::<f64>::one() + c / (previous * previous)).ln();
+        self.log = Some(log + term * (self.weight / 2.0));
+        self.weight /= 2.0;
+    }
+
+    /// Returns the logarithm of the Böttcher coordinate, or 0 when it wasn't tracked.
+    pub fn log(&self) -> Complex<f64> {
+        self.log.unwrap_or_default()
+    }
+}
+
+/// Returns the texture coordinates u, v in [0, 1) of a point with the given logarithm of
+/// its Böttcher coordinate.
+///
+/// v runs once through the texture per band of the potential, and u around the external
+/// angle in `TEXTURE_TILES` times two to the band's number tiles.
+pub fn texture_coordinates(log_bottcher: Complex<f64>) -> (f64, f64) {
+    let band = -log_bottcher.re.log2();
+    let turn = (log_bottcher.im / TAU).rem_euclid(1.0);
+    let tiles = TEXTURE_TILES * band.floor().max(0.0).exp2();
+    ((turn * tiles).rem_euclid(1.0), band.rem_euclid(1.0))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(start: Complex<f64>, c: Complex<f64>, steps: usize) -> Complex<f64> {
+        let mut tracker = BottcherTracker::new(true);
+        let mut z = start;
+        for _ in 0..steps {
+            let previous = z;
+            z = z * z + c;
+            tracker.add(previous, z, c);
+        }
+        tracker.log()
+    }
+
+    #[test]
+    fn test_julia_of_zero() {
+        // The Böttcher coordinate of z^2 is z itself.
+        let z = Complex::new(1.5, 1.0);
+        let log = track(z, Complex::zero(), 5);
+
+        assert!((log - z.ln()).norm() < 1e-12, "{log:?}");
+    }
+
+    #[test]
+    fn test_mandelbrot_far_away() {
+        // Far from the set the Böttcher coordinate is close to c + 1/2.
+        let c = Complex::new(0.0, 100.0);
+        let log = track(Complex::zero(), c, 4);
+
+        assert!((log - (c + 0.5).ln()).norm() < 1e-4, "{log:?}");
+        assert_eq!(Complex::zero(), BottcherTracker::new(false).log());
+    }
+
+    #[test]
+    fn test_texture_coordinates() {
+        let (u, v) = texture_coordinates(Complex::new(0.75, TAU / 24.0));
+
+        assert!((u - 0.5).abs() < 1e-12);
+        assert!((v - (-0.75f64.log2())).abs() < 1e-12);
+    }
+}
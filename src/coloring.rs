@@ -0,0 +1,528 @@
+use slint::Rgba8Pixel;
+
+use std::sync::Arc;
+
+use crate::{average::OrbitAverage, bottcher::texture_coordinates, complex::Complex, fractals::{EscapeRecord, Outcome, ESCAPE_RADIUS2}, lighting::Lighting, palette::Palette, texture::Texture};
+
+/// Color of pixels where the certified render can neither prove escape nor boundedness.
+pub const UNDETERMINED_PIXEL: Rgba8Pixel = Rgba8Pixel { r: 255, g: 0, b: 255, a: 255 };
+
+/// Color of escaped pixels away from the boundary in the boundary only mode.
+pub const EXTERIOR_PIXEL: Rgba8Pixel = Rgba8Pixel { r: 0, g: 0, b: 0, a: 0 };
+
+/// Width in pixels of the band around the boundary drawn by the boundary only mode.
+pub const BOUNDARY_WIDTH: f64 = 2.0;
+
+// Distance in pixels, as a power of two, that the distance mode maps to 1.
+const DISTANCE_RANGE_LOG2: f64 = 8.0;
+
+/// Selects how an escaped pixel is turned into a color.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColoringMode {
+    /// One grey level per iteration count, which shows hard bands.
+    #[default]
+    Banded,
+
+    /// Normalized (fractional) iteration count, giving continuous gradients.
+    Smooth,
+
+    /// Estimated distance to the boundary, which keeps thin filaments sharp at any
+    /// iteration count.
+    Distance,
+
+    /// Only the band within `BOUNDARY_WIDTH` pixels of the boundary, which shows
+    /// Julia sets that are dust.
+    Boundary,
+
+    /// Average of sin(density arg z) over the orbit.
+    StripeAverage,
+
+    /// Average of where |z| lies within the bounds of the triangle inequality.
+    TriangleInequality,
+
+    /// Average of the angles the orbit turns through.
+    Curvature,
+
+    /// Closest approach of the orbit to the orbit trap.
+    OrbitTrap,
+
+    /// The texture, or a checkerboard of the ends of the palette, wrapped around the set
+    /// along its Böttcher coordinates.
+    BottcherTexture,
+}
+
+impl ColoringMode {
+    /// Calculates the coloring value of an escaped record, normalized so that [0, 1]
+    /// covers the iteration range, the distances up to 256 pixels, or the boundary band.
+    ///
+    /// `pixel_size` is the width of a pixel in the coordinates of the fractal.
+    pub fn escaped_value(&self, iterations: i32, record: &EscapeRecord, pixel_size: f64) -> f64 {
+        match self {
+            ColoringMode::Banded => record.iterations as f64 / iterations as f64,
+            ColoringMode::Smooth => smooth_iteration(record.iterations, record.z) / iterations as f64,
+            ColoringMode::Distance => (record.distance_estimate() / pixel_size).ln_1p() / std::f64::consts::LN_2 / DISTANCE_RANGE_LOG2,
+            ColoringMode::Boundary => record.distance_estimate() / pixel_size / BOUNDARY_WIDTH,
+            ColoringMode::StripeAverage | ColoringMode::TriangleInequality | ColoringMode::Curvature => {
+                record.orbit().average.interpolated(escape_fraction(record.z))
+            },
+            ColoringMode::OrbitTrap => record.orbit().trap_distance,
+            ColoringMode::BottcherTexture => texture_coordinates(record.orbit().bottcher).1,
+        }
+    }
+
+    /// Returns the orbit average the kernels need to sum for this mode.
+    pub fn orbit_average(&self, stripe_density: f64) -> OrbitAverage {
+        match self {
+            ColoringMode::StripeAverage => OrbitAverage::Stripe { density: stripe_density },
+            ColoringMode::TriangleInequality => OrbitAverage::TriangleInequality,
+            ColoringMode::Curvature => OrbitAverage::Curvature,
+            _ => OrbitAverage::None,
+        }
+    }
+}
+
+// Spreads whole numbers over [0, 1] so that neighbouring ones get distant colors.
+const GOLDEN_RATIO_FRACTION: f64 = 0.618_033_988_749_895;
+
+/// Selects how a pixel that never escaped is turned into a color.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InteriorMode {
+    /// The palette's interior color.
+    #[default]
+    Flat,
+
+    /// Period of the attracting cycle, which colors each hyperbolic component.
+    Period,
+
+    /// Angle of the cycle's multiplier, which shows the internal angles of a component.
+    MultiplierAngle,
+
+    /// Magnitude of the cycle's multiplier, which is 0 at a component's center and 1 at its edge.
+    MultiplierMagnitude,
+
+    /// Iteration with the smallest |z|, which colors the atom domains.
+    AtomDomain,
+
+    /// Point of the attracting cycle the orbit approaches, which separates the parts of
+    /// a Julia set's basin.
+    CycleIndex,
+
+    /// Closest approach of the orbit to the orbit trap.
+    OrbitTrap,
+}
+
+impl InteriorMode {
+    /// Calculates the coloring value of a bounded record, or None if it has no attracting
+    /// cycle to color by.
+    pub fn bounded_value(&self, record: &EscapeRecord) -> Option<f64> {
+        let spread = |n: i32| (n as f64 * GOLDEN_RATIO_FRACTION).fract();
+        let cycle = record.period > 0;
+        match self {
+            InteriorMode::Flat => None,
+            InteriorMode::Period => cycle.then(|| spread(record.period)),
+            InteriorMode::MultiplierAngle => cycle.then(|| record.multiplier.arg() / std::f64::consts::TAU + 0.5),
+            InteriorMode::MultiplierMagnitude => cycle.then(|| record.multiplier.norm()),
+            InteriorMode::AtomDomain => (record.atom_period > 0).then(|| spread(record.atom_period)),
+            InteriorMode::CycleIndex => cycle.then(|| record.cycle_index as f64 / record.period as f64),
+            InteriorMode::OrbitTrap => Some(record.orbit().trap_distance),
+        }
+    }
+}
+
+// Brightness of the dark parts of an angle decomposition.
+const DECOMPOSITION_DARK: f64 = 0.5;
+
+// Width of a field line, as a fraction of the angle between two lines.
+const FIELD_LINE_WIDTH: f64 = 0.15;
+
+/// Shades escaped pixels by the angle of the final z, which shows the external angles.
+///
+/// The shading scales the brightness of the coloring mode's color, so it combines with
+/// any mode, including the smooth iteration count.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Decomposition {
+    #[default]
+    Off,
+
+    /// Darkens the half of each band where z is below the real axis.
+    Binary,
+
+    /// Splits each band into this many sectors by angle, darkening from the first to the last.
+    Sectors(u32),
+
+    /// Draws this many lines along the external rays where a band starts, doubling to twice
+    /// as many where it ends, so the lines carry on into the next band without a seam.
+    FieldLines(u32),
+}
+
+impl Decomposition {
+    /// Returns the brightness in [0, 1] of an escaped record with final value `z`.
+    pub fn brightness(&self, z: Complex<f64>) -> f64 {
+        // Fraction of a turn counter clockwise from the positive real axis, in [0, 1).
+        let turn = (z.arg() / std::f64::consts::TAU).rem_euclid(1.0);
+        match *self {
+            Decomposition::Off => 1.0,
+            Decomposition::Binary => if z.im >= 0.0 { 1.0 } else { DECOMPOSITION_DARK },
+            Decomposition::Sectors(sectors) => {
+                let sectors = sectors.max(2);
+                let sector = ((turn * sectors as f64) as u32).min(sectors - 1);
+                1.0 - (1.0 - DECOMPOSITION_DARK) * sector as f64 / (sectors - 1) as f64
+            },
+            Decomposition::FieldLines(lines) => {
+                // The angle of z doubles from one band to the next, so the lines of the end
+                // of a band are those of the start of the next at twice the count.
+                let line = |count: f64| {
+                    let position = turn * count;
+                    1.0 - ((position - position.round()).abs() / FIELD_LINE_WIDTH).min(1.0)
+                };
+                let lines = lines.max(1) as f64;
+                let fraction = escape_fraction(z);
+                let line = line(lines) * (1.0 - fraction) + line(2.0 * lines) * fraction;
+                1.0 - (1.0 - DECOMPOSITION_DARK) * line
+            },
+        }
+    }
+}
+
+/// Distribution of the coloring values over an image, used for histogram equalization.
+///
+/// Mapping each value to its rank spreads the colors evenly over the pixels, even when
+/// most of them escape within a narrow band of iterations.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Equalizer {
+    sorted: Vec<f64>,
+}
+
+impl Equalizer {
+    pub fn new(values: impl Iterator<Item = f64>) -> Self {
+        let mut sorted: Vec<f64> = values.filter(|value| value.is_finite()).collect();
+        sorted.sort_by(f64::total_cmp);
+        Equalizer { sorted }
+    }
+
+    /// Returns the fraction of values in the distribution below `value`, in [0, 1].
+    ///
+    /// Equal values share the middle of their ranks, so a banded image keeps one color per band.
+    pub fn rank(&self, value: f64) -> f64 {
+        if self.sorted.is_empty() {
+            return value;
+        }
+        let below = self.sorted.partition_point(|&v| v < value);
+        let through = self.sorted.partition_point(|&v| v <= value);
+        (below + through) as f64 / 2.0 / self.sorted.len() as f64
+    }
+}
+
+/// The coloring stage, which maps the records of the compute stage to colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colorer {
+    /// Iteration limit the records were computed with.
+    iterations: i32,
+    mode: ColoringMode,
+    palette: Palette,
+
+    /// Distribution of the image's values, when equalizing.
+    equalizer: Option<Equalizer>,
+
+    /// Blend from the linear (0) to the equalized (1) value.
+    equalize: f64,
+
+    /// Width of a pixel in the coordinates of the fractal, for distance based modes.
+    pixel_size: f64,
+
+    interior: InteriorMode,
+
+    decomposition: Decomposition,
+
+    /// Image an image orbit trap or the Böttcher texture mapping samples, when set.
+    texture: Option<Arc<Texture>>,
+
+    /// Shades escaped pixels as a lit surface, when set.
+    lighting: Option<Lighting>,
+}
+
+impl Colorer {
+    pub fn new(iterations: i32, mode: ColoringMode, palette: Palette) -> Self {
+        Colorer { iterations, mode, palette, equalizer: None, equalize: 0.0, pixel_size: 1.0, interior: InteriorMode::Flat, decomposition: Decomposition::Off, texture: None, lighting: None }
+    }
+
+    /// Sets the width of a pixel in the coordinates of the fractal, which converts
+    /// distance estimates to pixels.
+    pub fn with_pixel_size(mut self, pixel_size: f64) -> Self {
+        self.pixel_size = pixel_size;
+        self
+    }
+
+    pub fn with_interior(mut self, interior: InteriorMode) -> Self {
+        self.interior = interior;
+        self
+    }
+
+    pub fn with_decomposition(mut self, decomposition: Decomposition) -> Self {
+        self.decomposition = decomposition;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Option<Arc<Texture>>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn with_lighting(mut self, lighting: Option<Lighting>) -> Self {
+        self.lighting = lighting;
+        self
+    }
+
+    /// Equalizes the coloring values over the given records, blended with the linear
+    /// values by `equalize` in [0, 1].
+    ///
+    /// This is the first pass of histogram equalization, which collects the distribution
+    /// of escaped values. Coloring the records afterwards is the second pass.
+    pub fn equalized(mut self, records: &[EscapeRecord], equalize: f64) -> Self {
+        self.equalize = equalize.clamp(0.0, 1.0);
+        self.equalizer = (self.equalize > 0.0).then(|| {
+            let escaped = records.iter().filter(|record| record.outcome == Outcome::Escaped);
+            Equalizer::new(escaped.map(|record| self.value(record)))
+        });
+        self
+    }
+
+    // Calculates the linear coloring value of an escaped record.
+    fn value(&self, record: &EscapeRecord) -> f64 {
+        self.mode.escaped_value(self.iterations, record, self.pixel_size)
+    }
+
+    /// Calculates the color of a single record.
+    pub fn color(&self, record: &EscapeRecord) -> Rgba8Pixel {
+        match record.outcome {
+            Outcome::Escaped => {
+                let value = self.value(record);
+                if self.mode == ColoringMode::Boundary && (value > 1.0 || value.is_nan()) {
+                    return EXTERIOR_PIXEL;
+                }
+                let value = match &self.equalizer {
+                    Some(equalizer) => value + (equalizer.rank(value) - value) * self.equalize,
+                    None => value,
+                };
+                let color = match self.mode {
+                    ColoringMode::OrbitTrap => self.texture_color(record).unwrap_or_else(|| self.palette.color(value)),
+                    ColoringMode::BottcherTexture => self.bottcher_color(record),
+                    _ => self.palette.color(value),
+                };
+                let color = darken(color, self.decomposition.brightness(record.z));
+                match &self.lighting {
+                    Some(lighting) => lighting.shade(color, record),
+                    None => color,
+                }
+            },
+            Outcome::Bounded => match (self.interior, self.interior.bounded_value(record)) {
+                (InteriorMode::OrbitTrap, Some(value)) => self.texture_color(record).unwrap_or_else(|| self.palette.color(value)),
+                (_, Some(value)) => self.palette.color(value),
+                (_, None) => self.palette.interior,
+            },
+            Outcome::Undetermined => UNDETERMINED_PIXEL,
+        }
+    }
+
+    // Samples the texture, or a checkerboard, at the Böttcher coordinates of an escaped record.
+    fn bottcher_color(&self, record: &EscapeRecord) -> Rgba8Pixel {
+        let (u, v) = texture_coordinates(record.orbit().bottcher);
+        match &self.texture {
+            Some(texture) => texture.sample(u, v),
+            None if (u < 0.5) == (v < 0.5) => self.palette.color(0.0),
+            None => self.palette.color(1.0),
+        }
+    }
+
+    // Samples the image of an image orbit trap where the orbit landed in it.
+    fn texture_color(&self, record: &EscapeRecord) -> Option<Rgba8Pixel> {
+        let texture = self.texture.as_ref()?;
+        let orbit = record.orbit();
+        (orbit.trap_distance == 0.0).then(|| {
+            let point = orbit.trap_point;
+            texture.sample((point.re + 1.0) / 2.0, (1.0 - point.im) / 2.0)
+        })
+    }
+}
+
+/// Scales the color channels of a pixel by `brightness` in [0, 1], keeping its alpha.
+pub fn darken(color: Rgba8Pixel, brightness: f64) -> Rgba8Pixel {
+    if brightness >= 1.0 {
+        return color;
+    }
+    let channel = |value: u8| (value as f64 * brightness).round() as u8;
+    Rgba8Pixel::new(channel(color.r), channel(color.g), channel(color.b), color.a)
+}
+
+/// Returns the normalized iteration count i + 1 - log2(ln|z|) of a point that escaped on
+/// iteration `i` with final value `z`.
+///
+/// Escaping one iteration later squares |z|, which doubles ln|z| and so exactly makes up
+/// for the extra iteration. This makes the count continuous across the bands.
+pub fn smooth_iteration(i: i32, z: Complex<f64>) -> f64 {
+    let log_z = z.norm_sqr().ln() / 2.0;
+    i as f64 + 1.0 - log_z.log2()
+}
+
+/// Returns how far the final iteration of an escaped orbit went past the escape radius
+/// R, from 1 at |z| = R down to 0 at |z| = R^2.
+///
+/// A point that just escaped is at 1, while its neighbour that escaped one iteration
+/// later is at 0, which makes blends between the last two iterations continuous.
+pub fn escape_fraction(z: Complex<f64>) -> f64 {
+    let log_radius = ESCAPE_RADIUS2.ln() / 2.0;
+    let log_z = z.norm_sqr().ln() / 2.0;
+    (1.0 + (log_radius / log_z).log2()).clamp(0.0, 1.0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::fractals::OrbitRecord;
+
+    use super::*;
+
+    #[test]
+    fn test_smooth_iteration_continuous() {
+        let z = Complex::new(300.0, 40.0);
+
+        let before = smooth_iteration(5, z);
+        let after = smooth_iteration(6, z * z);
+        assert!((before - after).abs() < 1e-9, "{before} != {after}");
+    }
+
+    #[test]
+    fn test_many_iterations_not_black() {
+        let record = EscapeRecord { outcome: Outcome::Escaped, iterations: 500, z: Complex::new(3.0, 0.0), ..Default::default() };
+
+        assert_eq!(0.5, ColoringMode::Banded.escaped_value(1000, &record, 1.0));
+        assert!(ColoringMode::Smooth.escaped_value(1000, &record, 1.0) > 0.0);
+    }
+
+    #[test]
+    fn test_colorer_outcomes() {
+        let palette = Palette::default();
+        let colorer = Colorer::new(100, ColoringMode::Smooth, palette.clone());
+        let record = EscapeRecord { outcome: Outcome::Undetermined, ..Default::default() };
+
+        assert_eq!(UNDETERMINED_PIXEL, colorer.color(&record));
+        assert_eq!(palette.interior, colorer.color(&EscapeRecord::default()));
+    }
+
+    #[test]
+    fn test_equalizer_rank() {
+        let equalizer = Equalizer::new([0.1, 0.1, 0.1, 0.2, 0.9].into_iter());
+
+        assert_eq!(0.3, equalizer.rank(0.1));
+        assert_eq!(0.7, equalizer.rank(0.2));
+        assert_eq!(0.8, equalizer.rank(0.5));
+        assert_eq!(1.0, equalizer.rank(1.0));
+        assert_eq!(0.25, Equalizer::default().rank(0.25));
+    }
+
+    #[test]
+    fn test_colorer_equalized() {
+        let record = |iterations| EscapeRecord { outcome: Outcome::Escaped, iterations, ..Default::default() };
+        let records = [record(1), record(2), record(3), record(100)];
+        let palette = Palette::default();
+
+        let linear = Colorer::new(100, ColoringMode::Banded, palette.clone()).equalized(&records, 0.0);
+        let equalized = Colorer::new(100, ColoringMode::Banded, palette.clone()).equalized(&records, 1.0);
+        assert_eq!(palette.color(0.03), linear.color(&records[2]));
+        assert_eq!(palette.color(0.625), equalized.color(&records[2]));
+    }
+
+    #[test]
+    fn test_boundary_only() {
+        // z = 256 and dz = 256 ln 256 / 3 are 3 units from the boundary.
+        let z = Complex::new(256.0, 0.0);
+        let dz = Complex::new(256.0 * 256f64.ln() / 3.0, 0.0);
+        let record = EscapeRecord { outcome: Outcome::Escaped, z, dz, ..Default::default() };
+        let palette = Palette::default();
+
+        let far = Colorer::new(100, ColoringMode::Boundary, palette.clone()).with_pixel_size(1.0);
+        let near = Colorer::new(100, ColoringMode::Boundary, palette.clone()).with_pixel_size(2.0);
+        assert_eq!(EXTERIOR_PIXEL, far.color(&record));
+        assert_eq!(palette.color(0.75), near.color(&record));
+    }
+
+    #[test]
+    fn test_interior_modes() {
+        let record = EscapeRecord { period: 3, cycle_index: 1, multiplier: Complex::new(0.0, 0.5), atom_period: 3, ..Default::default() };
+
+        assert_eq!(None, InteriorMode::Flat.bounded_value(&record));
+        assert_eq!(Some(0.75), InteriorMode::MultiplierAngle.bounded_value(&record));
+        assert_eq!(Some(0.5), InteriorMode::MultiplierMagnitude.bounded_value(&record));
+        assert_eq!(InteriorMode::Period.bounded_value(&record), InteriorMode::AtomDomain.bounded_value(&record));
+        assert_eq!(Some(1.0 / 3.0), InteriorMode::CycleIndex.bounded_value(&record));
+        assert_eq!(None, InteriorMode::Period.bounded_value(&EscapeRecord::default()));
+    }
+
+    #[test]
+    fn test_escape_fraction() {
+        assert_eq!(1.0, escape_fraction(Complex::new(256.0, 0.0)));
+        assert_eq!(0.0, escape_fraction(Complex::new(0.0, 65536.0)));
+        assert!((escape_fraction(Complex::new(4096.0, 0.0)) - (1.0 + (8.0f64 / 12.0).log2())).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_decomposition() {
+        let above = Complex::new(-1.0, 0.1);
+        let below = Complex::new(-1.0, -0.1);
+
+        assert_eq!(1.0, Decomposition::Binary.brightness(above));
+        assert_eq!(DECOMPOSITION_DARK, Decomposition::Binary.brightness(below));
+        assert_eq!(1.0, Decomposition::Sectors(4).brightness(Complex::new(1.0, 0.1)));
+        assert_eq!(DECOMPOSITION_DARK, Decomposition::Sectors(4).brightness(Complex::new(1.0, -0.1)));
+        assert_eq!(DECOMPOSITION_DARK, Decomposition::FieldLines(4).brightness(Complex::new(0.0, 1e6)));
+        assert_eq!(1.0, Decomposition::FieldLines(4).brightness(Complex::new(1e6, 1e6)));
+    }
+
+    #[test]
+    fn test_field_lines_continuous() {
+        // A point escaping just past the radius and its neighbor across the band boundary,
+        // which escaped one iteration later at the square of that.
+        for angle in [0.1_f64, 0.9, 2.0, 3.1, -1.3] {
+            let z = Complex::new(angle.cos(), angle.sin()) * 256.0001;
+            let (end, start) = (Decomposition::FieldLines(5).brightness(z), Decomposition::FieldLines(5).brightness(z * z));
+            assert!((end - start).abs() < 1e-3, "{angle}: {end} {start}");
+        }
+    }
+
+    #[test]
+    fn test_decomposition_with_smooth() {
+        let record = EscapeRecord { outcome: Outcome::Escaped, iterations: 10, z: Complex::new(300.0, -40.0), ..Default::default() };
+        let palette = Palette::default();
+        let plain = Colorer::new(100, ColoringMode::Smooth, palette.clone());
+        let binary = Colorer::new(100, ColoringMode::Smooth, palette).with_decomposition(Decomposition::Binary);
+
+        assert_eq!(darken(plain.color(&record), DECOMPOSITION_DARK), binary.color(&record));
+    }
+
+    #[test]
+    fn test_image_trap() {
+        let red = Rgba8Pixel::new(255, 0, 0, 255);
+        let texture = Arc::new(Texture::new(1, 1, vec![red]).unwrap());
+        let colorer = Colorer::new(100, ColoringMode::OrbitTrap, Palette::default()).with_texture(Some(texture));
+        let record = |trap_distance: f64| {
+            let orbit = OrbitRecord { trap_distance, ..Default::default() };
+            EscapeRecord { outcome: Outcome::Escaped, orbit: Some(Box::new(orbit)), ..Default::default() }
+        };
+        let (trapped, missed) = (record(0.0), record(0.25));
+
+        assert_eq!(red, colorer.color(&trapped));
+        assert_eq!(Palette::default().color(0.25), colorer.color(&missed));
+    }
+
+    #[test]
+    fn test_bottcher_checkerboard() {
+        let colorer = Colorer::new(100, ColoringMode::BottcherTexture, Palette::default());
+        let record = |angle: f64| {
+            let orbit = OrbitRecord { bottcher: Complex::new(0.75, angle), ..Default::default() };
+            EscapeRecord { outcome: Outcome::Escaped, orbit: Some(Box::new(orbit)), ..Default::default() }
+        };
+
+        assert_eq!(Palette::default().color(0.0), colorer.color(&record(0.1)));
+        assert_eq!(Palette::default().color(1.0), colorer.color(&record(0.4)));
+    }
+}
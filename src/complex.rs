@@ -0,0 +1,343 @@
+use std::ops;
+
+use num_traits::Float;
+
+use crate::{CoordinateSpace, Point};
+
+/// A complex number `re + im * i`.
+///
+/// The arithmetic operators only need the matching operators on `T`, so the
+/// type also works for non-float scalars. The transcendental functions need
+/// `T: Float`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex<T> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T> Complex<T> {
+    pub fn new(re: T, im: T) -> Self {
+        Complex { re, im }
+    }
+}
+
+impl<T> Complex<T> where
+    T: Copy + ops::Add<Output = T> + ops::Sub<Output = T> + ops::Mul<Output = T> {
+
+    /// Returns |z|^2, which avoids the square root of `norm`.
+    #[inline]
+    pub fn norm_sqr(&self) -> T {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Returns z^2.
+    #[inline]
+    pub fn square(&self) -> Self {
+        let re_im = self.re * self.im;
+        Complex::new(self.re * self.re - self.im * self.im, re_im + re_im)
+    }
+}
+
+impl<T: Copy + ops::Neg<Output = T>> Complex<T> {
+    /// Returns the complex conjugate.
+    #[inline]
+    pub fn conj(&self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+impl<T: Float> Complex<T> {
+    pub fn zero() -> Self {
+        Complex::new(T::zero(), T::zero())
+    }
+
+    pub fn one() -> Self {
+        Complex::new(T::one(), T::zero())
+    }
+
+    pub fn i() -> Self {
+        Complex::new(T::zero(), T::one())
+    }
+
+    /// Creates a complex number from its modulus and argument.
+    pub fn from_polar(r: T, theta: T) -> Self {
+        Complex::new(r * theta.cos(), r * theta.sin())
+    }
+
+    /// Returns |z|.
+    #[inline]
+    pub fn norm(&self) -> T {
+        self.re.hypot(self.im)
+    }
+
+    /// Returns the argument of z in the range (-pi, pi].
+    #[inline]
+    pub fn arg(&self) -> T {
+        self.im.atan2(self.re)
+    }
+
+    /// Returns 1/z.
+    pub fn recip(&self) -> Self {
+        let norm_sqr = self.norm_sqr();
+        Complex::new(self.re / norm_sqr, -self.im / norm_sqr)
+    }
+
+    /// Returns z^n by repeated squaring.
+    pub fn powi(&self, n: i32) -> Self {
+        if n < 0 {
+            return self.powi(-n).recip();
+        }
+
+        let mut result = Complex::one();
+        let mut base = *self;
+        let mut exp = n;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result *= base;
+            }
+            base = base.square();
+            exp >>= 1;
+        }
+        result
+    }
+
+    /// Returns z^p for a real power p, using the principal branch.
+    pub fn powf(&self, p: T) -> Self {
+        if self.re.is_zero() && self.im.is_zero() {
+            return Complex::zero();
+        }
+        Complex::from_polar(self.norm().powf(p), self.arg() * p)
+    }
+
+    /// Returns z^w for a complex power w, using the principal branch.
+    pub fn powc(&self, w: Self) -> Self {
+        if self.re.is_zero() && self.im.is_zero() {
+            return Complex::zero();
+        }
+        (self.ln() * w).exp()
+    }
+
+    /// Returns the principal square root.
+    pub fn sqrt(&self) -> Self {
+        let two = T::one() + T::one();
+        Complex::from_polar(self.norm().sqrt(), self.arg() / two)
+    }
+
+    /// Returns e^z.
+    pub fn exp(&self) -> Self {
+        Complex::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Returns the principal natural logarithm.
+    pub fn ln(&self) -> Self {
+        Complex::new(self.norm().ln(), self.arg())
+    }
+
+    pub fn sin(&self) -> Self {
+        Complex::new(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+    }
+
+    pub fn cos(&self) -> Self {
+        Complex::new(self.re.cos() * self.im.cosh(), -self.re.sin() * self.im.sinh())
+    }
+
+    pub fn tan(&self) -> Self {
+        self.sin() / self.cos()
+    }
+
+    pub fn sinh(&self) -> Self {
+        Complex::new(self.re.sinh() * self.im.cos(), self.re.cosh() * self.im.sin())
+    }
+
+    pub fn cosh(&self) -> Self {
+        Complex::new(self.re.cosh() * self.im.cos(), self.re.sinh() * self.im.sin())
+    }
+
+    pub fn tanh(&self) -> Self {
+        self.sinh() / self.cosh()
+    }
+}
+
+impl<T: ops::Add<Output = T>> ops::Add for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl<T: ops::Sub<Output = T>> ops::Sub for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl<T> ops::Mul for Complex<T> where
+    T: Copy + ops::Add<Output = T> + ops::Sub<Output = T> + ops::Mul<Output = T> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Complex::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re)
+    }
+}
+
+impl<T> ops::Div for Complex<T> where
+    T: Copy + ops::Add<Output = T> + ops::Sub<Output = T> + ops::Mul<Output = T> + ops::Div<Output = T> {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        let denominator = rhs.norm_sqr();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator)
+    }
+}
+
+impl<T: ops::Neg<Output = T>> ops::Neg for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl<T: ops::Add<Output = T>> ops::Add<T> for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: T) -> Self {
+        Complex::new(self.re + rhs, self.im)
+    }
+}
+
+impl<T: ops::Sub<Output = T>> ops::Sub<T> for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: T) -> Self {
+        Complex::new(self.re - rhs, self.im)
+    }
+}
+
+impl<T: Copy + ops::Mul<Output = T>> ops::Mul<T> for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: T) -> Self {
+        Complex::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl<T: Copy + ops::Div<Output = T>> ops::Div<T> for Complex<T> {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, rhs: T) -> Self {
+        Complex::new(self.re / rhs, self.im / rhs)
+    }
+}
+
+impl<T: Copy + ops::Add<Output = T>> ops::AddAssign for Complex<T> {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<T: Copy + ops::Sub<Output = T>> ops::SubAssign for Complex<T> {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T> ops::MulAssign for Complex<T> where
+    T: Copy + ops::Add<Output = T> + ops::Sub<Output = T> + ops::Mul<Output = T> {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+/// Treats the x, y coordinate of a point as the real and imaginary parts.
+impl<T: CoordinateSpace> From<&Point<T>> for Complex<f64> {
+    fn from(point: &Point<T>) -> Self {
+        Complex::new(point.x, point.y)
+    }
+}
+
+/// Places a complex number back in the plane of coordinate space T.
+impl<T: CoordinateSpace> From<Complex<f64>> for Point<T> {
+    fn from(z: Complex<f64>) -> Self {
+        Point::new(z.re, z.im)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-12;
+
+    fn assert_close(expected: Complex<f64>, actual: Complex<f64>) {
+        assert!((expected - actual).norm() < EPSILON, "Expected {expected:?}, got {actual:?}");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Complex::new(1.0, 2.0);
+        let b = Complex::new(3.0, -1.0);
+
+        assert_eq!(Complex::new(4.0, 1.0), a + b);
+        assert_eq!(Complex::new(-2.0, 3.0), a - b);
+        assert_eq!(Complex::new(5.0, 5.0), a * b);
+        assert_close(a, (a * b) / b);
+        assert_eq!(a * a, a.square());
+        assert_eq!(Complex::new(-1.0, -2.0), -a);
+        assert_eq!(5.0, a.norm_sqr());
+    }
+
+    #[test]
+    fn test_powers() {
+        let z = Complex::new(0.5, -0.75);
+
+        assert_close(Complex::one(), z.powi(0));
+        assert_close(z * z * z, z.powi(3));
+        assert_close((z * z * z).recip(), z.powi(-3));
+        assert_close(z * z * z, z.powf(3.0));
+        assert_close(z * z, z.powc(Complex::new(2.0, 0.0)));
+        assert_close(z, z.sqrt() * z.sqrt());
+    }
+
+    #[test]
+    fn test_transcendental() {
+        let z = Complex::new(0.3, 1.2);
+
+        assert_close(z, z.ln().exp());
+        assert_close(Complex::new(-1.0, 0.0), (Complex::i() * std::f64::consts::PI).exp());
+        assert_close(Complex::one(), z.sin() * z.sin() + z.cos() * z.cos());
+        assert_close(Complex::one(), z.cosh() * z.cosh() - z.sinh() * z.sinh());
+        assert_close(z.sin() / z.cos(), z.tan());
+    }
+
+    #[test]
+    fn test_point_conversion() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Scope {}
+        impl CoordinateSpace for Scope {}
+
+        let point = Point::<Scope>::new(-0.5, 0.25);
+        let z = Complex::from(&point);
+
+        assert_eq!(Complex::new(-0.5, 0.25), z);
+        assert_eq!(point, Point::from(z));
+    }
+}
@@ -0,0 +1,77 @@
+use std::ops::{Add, Mul};
+
+/// A dual number, which carries the derivative of a value along with it.
+///
+/// Arithmetic on dual numbers applies the chain rule, so iterating a formula on a dual
+/// number gives the derivative of the result with respect to whichever input was
+/// created as the variable.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Dual<T> {
+    pub value: T,
+    pub derivative: T,
+}
+
+impl<T> Dual<T> {
+    pub fn new(value: T, derivative: T) -> Self {
+        Dual { value, derivative }
+    }
+}
+
+impl<T: Default> Dual<T> {
+    /// Creates a value that doesn't depend on the variable.
+    pub fn constant(value: T) -> Self {
+        Dual { value, derivative: T::default() }
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Mul<Output = T>> Dual<T> {
+    /// Returns self * self, with derivative 2 value derivative.
+    pub fn square(self) -> Self {
+        let twice = self.value + self.value;
+        Dual { value: self.value * self.value, derivative: twice * self.derivative }
+    }
+}
+
+impl<T: Add<Output = T>> Add for Dual<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Dual { value: self.value + other.value, derivative: self.derivative + other.derivative }
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Mul<Output = T>> Mul for Dual<T> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Dual {
+            value: self.value * other.value,
+            derivative: self.value * other.derivative + self.derivative * other.value,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complex::Complex;
+
+    #[test]
+    fn test_product_rule() {
+        // d/dx (x^2 + 3) x at x = 2 is 3x^2 + 3 = 15.
+        let x = Dual::new(2.0, 1.0);
+        let result = (x.square() + Dual::constant(3.0)) * x;
+
+        assert_eq!(Dual::new(14.0, 15.0), result);
+    }
+
+    #[test]
+    fn test_complex_square() {
+        let z = Dual::new(Complex::new(1.0, 2.0), Complex::new(1.0, 0.0));
+        let squared = z.square();
+
+        assert_eq!(z * z, squared);
+        assert_eq!(Complex::new(2.0, 4.0), squared.derivative);
+    }
+}
@@ -1,11 +1,289 @@
 use slint::Rgba8Pixel;
-//use color::{Deg, Hsv, Rgb, ToRgb};
 
-use crate::{CoordinateSpace, Point};
+use crate::{average::{Averager, AverageSum, OrbitAverage}, bottcher::BottcherTracker, complex::Complex, dual::Dual, interior::{closed_form_cycle, find_cycle, PeriodicityChecker, MAX_PERIOD}, interval::Interval, render::Fill, simd::{escape_times, Family, Precision}, trap::{OrbitTrap, TrapTracker}, viewport::Cell, CoordinateSpace, Point};
 
-/// Calculates a pixel's color given an x, y coordinate in some coordinate space T
-pub trait Pixelator<T: CoordinateSpace> {
-    fn get_pixel(&self, point: &Point<T>) -> Rgba8Pixel;
+/// Calculates a pixel's value P given an x, y coordinate in some coordinate space T
+///
+/// Pixelators are shared by the threads rendering an image.
+pub trait Pixelator<T: CoordinateSpace, P = Rgba8Pixel>: Send + Sync {
+    fn get_pixel(&self, point: &Point<T>) -> P;
+
+    /// Calculates the pixels of many points at once, which pixelators that iterate several
+    /// points together override.
+    fn get_pixels(&self, points: &[Point<T>]) -> Vec<P> {
+        points.iter().map(|point| self.get_pixel(point)).collect()
+    }
+
+    /// Maps a point to its mirror image under the symmetry of the pixels, if they have one,
+    /// so the pixel of either point follows from the other by `mirror_pixel`.
+    fn mirror_point(&self, _point: &Point<T>) -> Option<Point<T>> {
+        None
+    }
+
+    /// Turns the pixel of a point's mirror image, see `mirror_point`, into that of the point.
+    fn mirror_pixel(&self, pixel: P) -> P {
+        pixel
+    }
+}
+
+/// Calculates a pixel's value P given the whole cell of some coordinate space T that the pixel covers.
+pub trait CellPixelator<T: CoordinateSpace, P = Rgba8Pixel>: Send + Sync {
+    fn get_cell_pixel(&self, cell: &Cell<T>) -> P;
+}
+
+/// Squared radius beyond which a point is considered escaped.
+///
+/// This is much larger than the minimal radius of 2 so that colorings based on the
+/// final z, such as the normalized iteration count, are accurate.
+pub const ESCAPE_RADIUS2: f64 = 256.0 * 256.0;
+
+/// What is known about a point once the kernel stops iterating it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Outcome {
+    /// The orbit left the escape radius.
+    Escaped,
+
+    /// The orbit did not escape within the iteration limit (or is proven bounded
+    /// for certified renders).
+    #[default]
+    Bounded,
+
+    /// A certified render could neither prove escape nor boundedness.
+    Undetermined,
+}
+
+/// Per-pixel result of the compute stage.
+///
+/// Holds everything the coloring stage needs, so colors can be changed without
+/// iterating again. What only some coloring modes need is tracked through `OrbitOptions`
+/// and kept in `orbit`, so records of the other modes stay small.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EscapeRecord {
+    pub outcome: Outcome,
+
+    /// Iteration on which the point escaped, or the number of iterations run.
+    pub iterations: i32,
+
+    /// Final value of z.
+    pub z: Complex<f64>,
+
+    /// Derivative of the final z with respect to c (Mandelbrot) or z0 (Julia).
+    pub dz: Complex<f64>,
+
+    /// Minimum |z| over the orbit, after the starting value.
+    pub min_norm: f64,
+
+    /// Iteration on which the minimum |z| was reached, which gives the atom domains.
+    pub atom_period: i32,
+
+    /// Period of the attracting cycle a bounded orbit converged to, or 0 if none was found.
+    pub period: i32,
+
+    /// Multiplier of the attracting cycle, see `Cycle`.
+    pub multiplier: Complex<f64>,
+
+    /// Point of the attracting cycle the final z is at, see `Cycle`.
+    pub cycle_index: i32,
+
+    /// What the kernel tracked along the orbit, if the options asked for anything.
+    pub orbit: Option<Box<OrbitRecord>>,
+}
+
+/// What a kernel tracked along an orbit as asked by its `OrbitOptions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitRecord {
+    /// Sum of the orbit average the kernel was asked for.
+    pub average: AverageSum,
+
+    /// Closest approach of the orbit to the orbit trap, in units of the trap's size.
+    pub trap_distance: f64,
+
+    /// Point of the orbit at its closest approach, in the coordinates of the trap.
+    pub trap_point: Complex<f64>,
+
+    /// Logarithm of the Böttcher coordinate of an escaped point, whose real part is the
+    /// potential and imaginary part the external angle.
+    pub bottcher: Complex<f64>,
+}
+
+/// Records of orbits that tracked nothing, which never come near a trap.
+impl Default for OrbitRecord {
+    fn default() -> Self {
+        OrbitRecord { average: AverageSum::default(), trap_distance: f64::INFINITY, trap_point: Complex::default(), bottcher: Complex::default() }
+    }
+}
+
+/// What the kernels track along each orbit besides z, for the coloring stage.
+///
+/// Everything is off by default, since each costs time on every iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OrbitOptions {
+    pub average: OrbitAverage,
+    pub trap: Option<OrbitTrap>,
+    pub bottcher: bool,
+}
+
+impl OrbitOptions {
+    /// Whether the kernels track anything, and so fill in the records' `orbit`.
+    pub fn tracks(&self) -> bool {
+        *self != OrbitOptions::default()
+    }
+}
+
+impl EscapeRecord {
+    /// Estimates the distance from an escaped point to the boundary of the set, in the
+    /// coordinates of the fractal, as |z| ln|z| / |dz|.
+    ///
+    /// The true distance is within a factor of two of the estimate. Records without a
+    /// derivative, such as certified ones, are infinitely far away.
+    pub fn distance_estimate(&self) -> f64 {
+        let norm = self.z.norm();
+        norm * norm.ln() / self.dz.norm()
+    }
+
+    /// What was tracked along the orbit, or the record of tracking nothing.
+    pub fn orbit(&self) -> OrbitRecord {
+        self.orbit.as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Fills in the attracting cycle of period up to `max_period` that a bounded orbit of
+    /// z^2 + c converged to, if any was found.
+    pub fn with_cycle(mut self, c: Complex<f64>, max_period: i32) -> Self {
+        if let Some(cycle) = find_cycle(self.z, c, max_period) {
+            self.period = cycle.period;
+            self.multiplier = cycle.multiplier;
+            self.cycle_index = cycle.index;
+        }
+        self
+    }
+
+    /// Completes the record of a bounded orbit that periodicity checking stopped at z_n,
+    /// with the period it found.
+    ///
+    /// z is stepped on around the cycle to where iterating all the way would have left it,
+    /// so the points of the cycle line up between pixels stopped on different iterations.
+    pub fn with_period(mut self, n: i32, period: i32, c: Dual<Complex<f64>>) -> Self {
+        let mut z = Dual::new(self.z, self.dz);
+        for _ in 0..(self.iterations + 1 - n).rem_euclid(period) {
+            z = z.square() + c;
+        }
+        (self.z, self.dz) = (z.value, z.derivative);
+        let record = self.with_cycle(c.value, period);
+        EscapeRecord { period: if record.period > 0 { record.period } else { period }, ..record }
+    }
+
+    /// Returns the record of a bounded orbit of the Mandelbrot set without iterating it,
+    /// for c in the main cardioid or the period 2 bulb. Its atom domain is taken to be
+    /// that of its cycle.
+    pub fn closed_form(c: Complex<f64>, iterations: i32) -> Option<Self> {
+        let z = closed_form_cycle(c, iterations)?;
+        let record = EscapeRecord { outcome: Outcome::Bounded, iterations, z, ..Default::default() }.with_cycle(c, 2);
+        let mut min_norm2 = f64::INFINITY;
+        let mut w = z;
+        for _ in 0..record.period {
+            w = w * w + c;
+            min_norm2 = min_norm2.min(w.norm_sqr());
+        }
+        Some(EscapeRecord { min_norm: min_norm2.sqrt(), atom_period: record.period, ..record })
+    }
+}
+
+/// Subdivision fills rectangles whose borders escaped on the same iteration or were all
+/// bounded, which is exact where those bands have no holes, as around the Mandelbrot set.
+impl Fill for EscapeRecord {
+    fn matches(&self, other: &Self) -> bool {
+        self.outcome == other.outcome && self.iterations == other.iterations
+    }
+}
+
+
+/// Iterates z = z^2 + c from the given start, carrying the derivative with respect to
+/// whichever of z and c is the variable.
+///
+/// Bounded orbits are searched for an attracting cycle. With `periodicity` checking they
+/// stop as soon as the orbit comes back to a point it passed.
+fn escape_time(iterations: i32, mut z: Dual<Complex<f64>>, c: Dual<Complex<f64>>, options: &OrbitOptions, periodicity: bool) -> EscapeRecord {
+    let (mut min_norm2, mut atom_period) = (f64::INFINITY, 0);
+    let mut checker = PeriodicityChecker::new(periodicity, z.value);
+    let mut averager = Averager::new(options.average, c.value);
+    let mut tracker = TrapTracker::new(options.trap);
+    let mut bottcher = BottcherTracker::new(options.bottcher);
+    let orbit = |averager: &Averager, tracker: &TrapTracker, bottcher: Complex<f64>| options.tracks().then(|| Box::new(OrbitRecord {
+        average: averager.sum(), trap_distance: tracker.distance, trap_point: tracker.point, bottcher,
+    }));
+    for i in 0..=iterations {
+        if z.value.norm_sqr() > ESCAPE_RADIUS2 {
+            return EscapeRecord {
+                outcome: Outcome::Escaped, iterations: i, z: z.value, dz: z.derivative,
+                min_norm: min_norm2.sqrt(), atom_period,
+                orbit: orbit(&averager, &tracker, bottcher.log()), ..Default::default()
+            };
+        }
+        let previous = z.value;
+        z = z.square() + c;
+        averager.add(previous, z.value);
+        tracker.add(z.value);
+        bottcher.add(previous, z.value, c.value);
+        if z.value.norm_sqr() < min_norm2 {
+            (min_norm2, atom_period) = (z.value.norm_sqr(), i + 1);
+        }
+        if let Some(period) = checker.check(i + 1, z.value) {
+            let record = EscapeRecord {
+                outcome: Outcome::Bounded, iterations, z: z.value, dz: z.derivative,
+                min_norm: min_norm2.sqrt(), atom_period,
+                orbit: orbit(&averager, &tracker, Complex::default()), ..Default::default()
+            };
+            return record.with_period(i + 1, period, c);
+        }
+    }
+
+    let record = EscapeRecord {
+        outcome: Outcome::Bounded, iterations, z: z.value, dz: z.derivative,
+        min_norm: min_norm2.sqrt(), atom_period,
+        orbit: orbit(&averager, &tracker, Complex::default()), ..Default::default()
+    };
+    record.with_cycle(c.value, iterations.clamp(1, MAX_PERIOD))
+}
+
+
+// Relative amount a box is widened by before testing whether the iteration traps it.
+const TRAP_INFLATION: f64 = 0.01;
+
+/// Iterates a box of starting points z over a box of constants c with interval arithmetic.
+///
+/// Returns the pixel as provably escaping, provably bounded or undetermined. A box is
+/// provably bounded when some iterate of an inflated trap box lands back inside that
+/// same trap, since every later iterate is then trapped in it as well. The trap is
+/// taken again at every power of two iterations, which catches attracting cycles of
+/// any period up to half the iteration count.
+///
+/// Escaped points are reported at the exact escape radius, with the midpoint of the box as z.
+fn certified_pixel(iterations: i32, mut z: Complex<Interval>, c: Complex<Interval>, escape_radius2: f64) -> EscapeRecord {
+    let mut trap: Option<(Complex<Interval>, Complex<Interval>)> = None;
+    let mut next_trap = 4;
+    for i in 0..=iterations {
+        if z.norm_sqr_bounds().lo > escape_radius2 {
+            let midpoint = Complex::new((z.re.lo + z.re.hi) / 2.0, (z.im.lo + z.im.hi) / 2.0);
+            return EscapeRecord { outcome: Outcome::Escaped, iterations: i, z: midpoint, ..Default::default() };
+        }
+        if let Some((trap_box, trap_iterate)) = &mut trap {
+            if trap_box.contains(trap_iterate) {
+                return EscapeRecord { outcome: Outcome::Bounded, iterations: i, ..Default::default() };
+            }
+            *trap_iterate = trap_iterate.sqr() + c;
+        }
+        if i == next_trap {
+            // Only a bounded box can prove the orbit is bounded.
+            if z.norm_sqr_bounds().hi <= escape_radius2 {
+                let trap_box = z.inflate(TRAP_INFLATION);
+                trap = Some((trap_box, trap_box.sqr() + c));
+            }
+            next_trap *= 2;
+        }
+        z = z.sqr() + c;
+    }
+
+    EscapeRecord { outcome: Outcome::Undetermined, iterations, ..Default::default() }
 }
 
 
@@ -13,40 +291,87 @@ pub trait Pixelator<T: CoordinateSpace> {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Mandelbrot {
     iterations: i32,
+
+    options: OrbitOptions,
+
+    precision: Precision,
+
+    /// Whether bounded orbits are stopped early by periodicity checking and the closed
+    /// form cardioid and bulb tests.
+    interior_checks: bool,
 }
 
 impl Mandelbrot {
     pub fn new(iterations: i32) -> Self {
-        Mandelbrot { iterations }
+        Mandelbrot { iterations, options: OrbitOptions::default(), precision: Precision::default(), interior_checks: false }
+    }
+
+    /// Sets what is tracked along each orbit for the coloring stage.
+    pub fn with_options(mut self, options: OrbitOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the precision points are iterated in when several are iterated together.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Sets whether bounded orbits are stopped early, which leaves out the part of the
+    /// orbit trap after the orbit was found periodic.
+    pub fn with_interior_checks(mut self, interior_checks: bool) -> Self {
+        self.interior_checks = interior_checks;
+        self
     }
 }
 
 /// Indicates that Mandelbrot is a coordinate space.
 impl CoordinateSpace for Mandelbrot {}
 
-impl Pixelator<Mandelbrot> for Mandelbrot {
+impl Pixelator<Mandelbrot, EscapeRecord> for Mandelbrot {
     /// Calculates each pixel of the mandelbrot fractal given the coordinate of that pixel.
-    fn get_pixel(&self, point: &Point<Mandelbrot>) -> Rgba8Pixel {
-        let c_r = point.x;
-        let c_i = point.y;
-        
-        let mut z_r = 0.0f64;
-        let mut z_i = 0.0f64;
-        for i in 0..=self.iterations {
-            let z_r2 = z_r * z_r;
-            let z_i2 = z_i * z_i;
-            let dist2 = z_r2 + z_i2;
-            if dist2 > 4.0 {
-                let color = (i*(255/self.iterations)) as u8;
-                return slint::Rgba8Pixel::new(color, color, color, 255);
+    fn get_pixel(&self, point: &Point<Mandelbrot>) -> EscapeRecord {
+        // Differentiating with respect to c gives dz/dc.
+        let c = Dual::new(Complex::from(point), Complex::one());
+        // The closed form tests don't see the orbit, which the orbit trap needs.
+        if self.interior_checks && self.options.trap.is_none() {
+            if let Some(record) = EscapeRecord::closed_form(c.value, self.iterations) {
+                return record;
             }
-            let new_z_r = z_r2 - z_i2 + c_r;
-            let new_z_i = 2.0 * z_r * z_i + c_i;
-            z_r = new_z_r;
-            z_i = new_z_i;
         }
-        
-        slint::Rgba8Pixel::new(0, 0, 0, 0)
+        escape_time(self.iterations, Dual::constant(Complex::zero()), c, &self.options, self.interior_checks)
+    }
+
+    /// Iterates the points several at a time with the vectorized kernels, which track
+    /// nothing along the orbits, so orbit options fall back to one point at a time.
+    fn get_pixels(&self, points: &[Point<Mandelbrot>]) -> Vec<EscapeRecord> {
+        if self.options.tracks() {
+            return points.iter().map(|point| self.get_pixel(point)).collect();
+        }
+        let points: Vec<Complex<f64>> = points.iter().map(Complex::from).collect();
+        escape_times(self.iterations, Family::Mandelbrot, self.precision, self.interior_checks, &points)
+    }
+
+    /// The set is symmetric about the real axis, but orbit options such as traps need not be.
+    fn mirror_point(&self, point: &Point<Mandelbrot>) -> Option<Point<Mandelbrot>> {
+        (!self.options.tracks()).then(|| Point::new(point.x, -point.y))
+    }
+
+    /// The orbit of the conjugate c is the conjugate orbit.
+    fn mirror_pixel(&self, record: EscapeRecord) -> EscapeRecord {
+        EscapeRecord { z: record.z.conj(), dz: record.dz.conj(), multiplier: record.multiplier.conj(), ..record }
+    }
+}
+
+impl CellPixelator<Mandelbrot, EscapeRecord> for Mandelbrot {
+    /// Certifies the whole cell of constants c as escaping or bounded.
+    ///
+    /// Any orbit with |z| > 2 escapes, so the escape radius is exact.
+    fn get_cell_pixel(&self, cell: &Cell<Mandelbrot>) -> EscapeRecord {
+        let c = Complex::from(cell);
+        let z = Complex::from(Complex::<f64>::zero());
+        certified_pixel(self.iterations, z, c, 4.0)
     }
 }
 
@@ -58,39 +383,191 @@ pub struct Julia {
     /// Number of iterations to run the function for.
     iterations: i32,
 
-    /// Complex constant c = (c_r + c_i * i) used to calculate the julia function z = z^2 + c
-    c_r: f64, c_i: f64,
+    /// Complex constant c used to calculate the julia function z = z^2 + c
+    c: Complex<f64>,
+
+    options: OrbitOptions,
+
+    precision: Precision,
+
+    /// Whether bounded orbits are stopped early by periodicity checking.
+    interior_checks: bool,
 }
 
 impl Julia {
     pub fn new(iterations: i32, c_r: f64, c_i: f64) -> Self {
-        Julia { iterations, c_r, c_i }
+        Julia { iterations, c: Complex::new(c_r, c_i), options: OrbitOptions::default(), precision: Precision::default(), interior_checks: false }
+    }
+
+    /// Sets what is tracked along each orbit for the coloring stage.
+    pub fn with_options(mut self, options: OrbitOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the precision points are iterated in when several are iterated together.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Sets whether bounded orbits are stopped early, which leaves out the part of the
+    /// orbit trap after the orbit was found periodic.
+    pub fn with_interior_checks(mut self, interior_checks: bool) -> Self {
+        self.interior_checks = interior_checks;
+        self
     }
 }
 
 /// Indicates that Julia is a coordinate space.
 impl CoordinateSpace for Julia {}
 
-impl Pixelator<Julia> for Julia {
+impl Pixelator<Julia, EscapeRecord> for Julia {
     /// Calculates each pixel of the Julia fractal given the coordinate of that pixel.
-    fn get_pixel(&self, point: &Point<Julia>) -> Rgba8Pixel {
-        let mut z_r = point.x;
-        let mut z_i = point.y;
-        
-        for i in 0..=self.iterations {
-            let z_r2 = z_r * z_r;
-            let z_i2 = z_i * z_i;
-            let dist2 = z_r2 + z_i2;
-            if dist2 > 4.0 {
-                let color = (i*(255/self.iterations)) as u8;
-                return slint::Rgba8Pixel::new(color, color, color, 255);
-            }
-            let new_z_r = z_r2 - z_i2 + self.c_r;
-            let new_z_i = 2.0 * z_r * z_i + self.c_i;
-            z_r = new_z_r;
-            z_i = new_z_i;
+    fn get_pixel(&self, point: &Point<Julia>) -> EscapeRecord {
+        // Differentiating with respect to the starting point gives dz/dz0.
+        let z = Dual::new(Complex::from(point), Complex::one());
+        escape_time(self.iterations, z, Dual::constant(self.c), &self.options, self.interior_checks)
+    }
+
+    /// Iterates the points several at a time, like the Mandelbrot set does.
+    fn get_pixels(&self, points: &[Point<Julia>]) -> Vec<EscapeRecord> {
+        if self.options.tracks() {
+            return points.iter().map(|point| self.get_pixel(point)).collect();
+        }
+        let points: Vec<Complex<f64>> = points.iter().map(Complex::from).collect();
+        escape_times(self.iterations, Family::Julia(self.c), self.precision, self.interior_checks, &points)
+    }
+
+    /// Quadratic Julia sets are symmetric under a half turn about the origin.
+    fn mirror_point(&self, point: &Point<Julia>) -> Option<Point<Julia>> {
+        (!self.options.tracks()).then(|| Point::new(-point.x, -point.y))
+    }
+
+    /// Orbits starting at -z0 and z0 meet after the first iteration, from where only the
+    /// derivative's sign differs.
+    fn mirror_pixel(&self, record: EscapeRecord) -> EscapeRecord {
+        let z = if record.outcome == Outcome::Escaped && record.iterations == 0 { -record.z } else { record.z };
+        EscapeRecord { z, dz: -record.dz, ..record }
+    }
+}
+
+impl CellPixelator<Julia, EscapeRecord> for Julia {
+    /// Certifies the whole cell of starting points z as escaping or bounded.
+    ///
+    /// An orbit only provably escapes once |z| > max(2, |c|).
+    fn get_cell_pixel(&self, cell: &Cell<Julia>) -> EscapeRecord {
+        let z = Complex::from(cell);
+        let c = Complex::from(self.c);
+        let escape_radius2 = c.norm_sqr_bounds().hi.max(4.0);
+        certified_pixel(self.iterations, z, c, escape_radius2)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell<T: CoordinateSpace>(x: f64, y: f64, size: f64) -> Cell<T> {
+        Cell { min: Point::new(x, y), max: Point::new(x + size, y + size) }
+    }
+
+    #[test]
+    fn test_certified_mandelbrot() {
+        let mandelbrot = Mandelbrot::new(100);
+
+        assert_eq!(Outcome::Bounded, mandelbrot.get_cell_pixel(&cell(-0.2, -0.1, 0.01)).outcome);
+        assert_eq!(Outcome::Bounded, mandelbrot.get_cell_pixel(&cell(-1.001, -0.0005, 0.001)).outcome);
+        assert_eq!(Outcome::Escaped, mandelbrot.get_cell_pixel(&cell(1.0, 1.0, 0.01)).outcome);
+        assert_eq!(Outcome::Undetermined, mandelbrot.get_cell_pixel(&cell(-0.76, 0.0, 0.1)).outcome);
+    }
+
+    #[test]
+    fn test_certified_julia() {
+        let julia = Julia::new(100, -0.1, 0.1);
+
+        assert_eq!(Outcome::Bounded, julia.get_cell_pixel(&cell(0.0, 0.0, 0.01)).outcome);
+        assert_eq!(Outcome::Escaped, julia.get_cell_pixel(&cell(1.5, 1.5, 0.01)).outcome);
+    }
+
+    #[test]
+    fn test_derivative() {
+        // Compare dz/dc against a finite difference of the final z.
+        let mandelbrot = Mandelbrot::new(5);
+        let h = 1e-7;
+        let record = mandelbrot.get_pixel(&Point::new(-0.3, 0.4));
+        let shifted = mandelbrot.get_pixel(&Point::new(-0.3 + h, 0.4));
+
+        let difference = (shifted.z - record.z) / h;
+        assert!((difference - record.dz).norm() < 1e-4 * record.dz.norm(), "{difference:?} != {:?}", record.dz);
+    }
+
+    #[test]
+    fn test_distance_estimate() {
+        // The Julia set of c = 0 is the unit circle.
+        let julia = Julia::new(100, 0.0, 0.0);
+        let estimate = julia.get_pixel(&Point::new(2.0, 0.0)).distance_estimate();
+        assert!((0.5..2.0).contains(&estimate), "{estimate}");
+
+        // The closest point of the Mandelbrot set to 2.5 is 0.25.
+        let estimate = Mandelbrot::new(100).get_pixel(&Point::new(2.5, 0.0)).distance_estimate();
+        assert!((2.25 / 2.0..2.25 * 2.0).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn test_interior_analysis() {
+        let record = Mandelbrot::new(200).get_pixel(&Point::new(-1.0, 0.0));
+        assert_eq!((Outcome::Bounded, 2, 2), (record.outcome, record.period, record.atom_period));
+
+        let julia = Julia::new(200, -1.0, 0.0);
+        let (a, b) = (julia.get_pixel(&Point::new(0.1, 0.0)), julia.get_pixel(&Point::new(-1.1, 0.0)));
+        assert_ne!(a.cycle_index, b.cycle_index);
+    }
+
+    #[test]
+    fn test_orbit_average() {
+        // Real orbits have no angle, so every stripe term is 1/2.
+        let julia = Julia::new(100, 0.0, 0.0).with_options(OrbitOptions { average: OrbitAverage::Stripe { density: 5.0 }, ..Default::default() });
+        let record = julia.get_pixel(&Point::new(2.0, 0.0));
+
+        assert_eq!(Outcome::Escaped, record.outcome);
+        assert_eq!(record.iterations, record.orbit().average.count);
+        assert_eq!(0.5, record.orbit().average.interpolated(0.3));
+    }
+
+    #[test]
+    fn test_untracked_orbit() {
+        let julia = Julia::new(100, 0.0, 0.0);
+        for point in [Point::new(2.0, 0.0), Point::new(0.5, 0.0)] {
+            let record = julia.get_pixel(&point);
+            assert_eq!(None, record.orbit);
+            assert_eq!(f64::INFINITY, record.orbit().trap_distance);
         }
-        
-        slint::Rgba8Pixel::new(0, 0, 0, 0)
+    }
+
+    #[test]
+    fn test_interior_checks() {
+        // The main cardioid, the period 2 bulb and, found by periodicity checking, the
+        // period 3 component of the rabbit.
+        for (point, period) in [((-0.1, 0.1), 1), ((-1.05, 0.05), 2), ((-0.12, 0.75), 3)] {
+            let point = Point::new(point.0, point.1);
+            let record = Mandelbrot::new(10000).get_pixel(&point);
+            let checked = Mandelbrot::new(10000).with_interior_checks(true).get_pixel(&point);
+
+            assert_eq!((Outcome::Bounded, period, period), (checked.outcome, checked.period, record.period));
+            assert_eq!(record.cycle_index, checked.cycle_index);
+            assert!((record.multiplier - checked.multiplier).norm() < 1e-6, "{:?} != {:?}", record.multiplier, checked.multiplier);
+            assert!((record.z - checked.z).norm() < 1e-6, "{:?} != {:?}", record.z, checked.z);
+        }
+
+        // Escaping points are iterated all the same.
+        let point = Point::new(-0.75, 0.1);
+        assert_eq!(Mandelbrot::new(1000).get_pixel(&point), Mandelbrot::new(1000).with_interior_checks(true).get_pixel(&point));
+
+        let julia = Julia::new(10000, -1.0, 0.0).with_interior_checks(true);
+        let (a, b) = (julia.get_pixel(&Point::new(0.1, 0.0)), julia.get_pixel(&Point::new(-1.1, 0.0)));
+        assert_eq!((2, 2), (a.period, b.period));
+        assert_ne!(a.cycle_index, b.cycle_index);
     }
 }
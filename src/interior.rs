@@ -0,0 +1,152 @@
+use crate::complex::Complex;
+
+/// Longest attracting cycle that is searched for.
+pub const MAX_PERIOD: i32 = 1024;
+
+// Distance within which an orbit is considered to have returned to the same point.
+const CYCLE_EPSILON: f64 = 1e-6;
+
+/// Distance within which periodicity checking takes an orbit to have come back to the
+/// point it saved. Much smaller than `CYCLE_EPSILON`, so the cycle is found again.
+pub const PERIODICITY_EPSILON: f64 = 1e-10;
+
+/// The attracting cycle of z -> z^2 + c that a bounded orbit has converged to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    pub period: i32,
+
+    /// Derivative of the whole cycle, which is the product of 2 z over its points.
+    /// Its magnitude is below 1 for an attracting cycle, and 0 at the center of a
+    /// hyperbolic component.
+    pub multiplier: Complex<f64>,
+
+    /// Which point of the cycle the orbit is at, counting from the point closest to
+    /// the critical point 0. Orbits iterated the same number of times that land on the
+    /// same point of a Julia set's cycle lie in the same part of its basin.
+    pub index: i32,
+}
+
+/// Finds the attracting cycle the orbit through `z` has converged to, trying periods
+/// up to `max_period`.
+///
+/// Returns None when the orbit hasn't come back to within `CYCLE_EPSILON` of `z`,
+/// which happens when it hasn't converged yet, for example close to the boundary.
+pub fn find_cycle(z: Complex<f64>, c: Complex<f64>, max_period: i32) -> Option<Cycle> {
+    let mut w = z;
+    let mut multiplier = Complex::<f64>::one();
+    let mut closest = (0, z.norm_sqr());
+    for period in 1..=max_period {
+        multiplier = multiplier * w * 2.0;
+        w = w * w + c;
+        if (w - z).norm_sqr() < CYCLE_EPSILON * CYCLE_EPSILON {
+            let index = (period - closest.0) % period;
+            return Some(Cycle { period, multiplier, index });
+        }
+        if w.norm_sqr() < closest.1 {
+            closest = (period, w.norm_sqr());
+        }
+    }
+    None
+}
+
+/// Brent's cycle detection along an orbit, which saves z on every power of two iteration
+/// and compares the iterates after it against it. A cycle of period p is found within
+/// about 2p iterations of the orbit converging to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodicityChecker {
+    enabled: bool,
+    saved: Complex<f64>,
+    saved_at: i32,
+    next_save: i32,
+}
+
+impl PeriodicityChecker {
+    /// Creates a checker for the orbit starting at z.
+    pub fn new(enabled: bool, z: Complex<f64>) -> Self {
+        PeriodicityChecker { enabled, saved: z, saved_at: 0, next_save: 1 }
+    }
+
+    /// Checks z on iteration n, returning the period once the orbit came back to the
+    /// saved point.
+    pub fn check(&mut self, n: i32, z: Complex<f64>) -> Option<i32> {
+        if !self.enabled {
+            return None;
+        }
+        if (z - self.saved).norm_sqr() < PERIODICITY_EPSILON * PERIODICITY_EPSILON {
+            return Some(n - self.saved_at);
+        }
+        if n == self.next_save {
+            (self.saved, self.saved_at, self.next_save) = (z, n, self.next_save.saturating_mul(2));
+        }
+        None
+    }
+}
+
+/// Finds the point of the attracting cycle that the orbit of 0 approaches after
+/// `iterations` + 1 steps, for c in the main cardioid or the period 2 bulb of the
+/// Mandelbrot set, which have closed form tests. Returns None for any other c.
+pub fn closed_form_cycle(c: Complex<f64>, iterations: i32) -> Option<Complex<f64>> {
+    let one = Complex::<f64>::one();
+    let x = c.re - 0.25;
+    let q = x * x + c.im * c.im;
+    if q * (q + x) < 0.25 * c.im * c.im {
+        // The attracting fixed point, (1 - sqrt(1 - 4c)) / 2.
+        return Some((one - (one - c * 4.0).sqrt()) * 0.5);
+    }
+    if (c.re + 1.0) * (c.re + 1.0) + c.im * c.im < 1.0 / 16.0 {
+        // The 2-cycle solves z^2 + z + c + 1 = 0, and the orbit of 0 is on the root
+        // through 0 at c = -1 after an even number of steps.
+        let root = (one * -3.0 - c * 4.0).sqrt();
+        let root = if (iterations + 1) % 2 == 0 { root } else { -root };
+        return Some((root - one) * 0.5);
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(z: Complex<f64>, c: Complex<f64>) -> Option<Cycle> {
+        // Let the orbit converge first.
+        let mut z = z;
+        for _ in 0..1000 {
+            z = z * z + c;
+        }
+        find_cycle(z, c, MAX_PERIOD)
+    }
+
+    #[test]
+    fn test_periods() {
+        let zero = Complex::<f64>::zero();
+
+        assert_eq!(1, cycle(zero, Complex::new(-0.1, 0.1)).unwrap().period);
+        assert_eq!(2, cycle(zero, Complex::new(-1.0, 0.0)).unwrap().period);
+        assert_eq!(3, cycle(zero, Complex::new(-0.12, 0.75)).unwrap().period);
+        assert_eq!(4, cycle(zero, Complex::new(-1.31, 0.0)).unwrap().period);
+        assert_eq!(None, find_cycle(zero, Complex::new(0.5, 0.0), 10));
+    }
+
+    #[test]
+    fn test_multiplier() {
+        // The fixed point of z^2 + c has multiplier 1 - sqrt(1 - 4c).
+        let c = Complex::new(-0.1, 0.1);
+        let expected = Complex::<f64>::one() - (Complex::<f64>::one() - c * 4.0).sqrt();
+        let found = cycle(Complex::zero(), c).unwrap();
+
+        assert!((found.multiplier - expected).norm() < 1e-5, "{:?} != {expected:?}", found.multiplier);
+        assert_eq!(0.0, cycle(Complex::zero(), Complex::new(-1.0, 0.0)).unwrap().multiplier.norm());
+    }
+
+    #[test]
+    fn test_cycle_index() {
+        // The basin of the 2-cycle of z^2 - 1 has parts around 0 and around -1.
+        let c = Complex::new(-1.0, 0.0);
+        let near_zero = cycle(Complex::new(0.1, 0.0), c).unwrap();
+        let near_minus_one = cycle(Complex::new(-1.1, 0.0), c).unwrap();
+
+        assert_eq!(near_zero.index, cycle(Complex::new(0.0, 0.2), c).unwrap().index);
+        assert_ne!(near_zero.index, near_minus_one.index);
+    }
+}
@@ -0,0 +1,186 @@
+use std::ops;
+
+use crate::{complex::Complex, viewport::Cell, CoordinateSpace};
+
+/// A closed interval [lo, hi] of real numbers.
+///
+/// Every operation rounds its bounds outward by one ulp, so the true result for
+/// any real numbers inside the operands is always inside the resulting interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Self {
+        Interval { lo: lo.min(hi), hi: lo.max(hi) }
+    }
+
+    /// Creates the degenerate interval [x, x].
+    pub fn point(x: f64) -> Self {
+        Interval { lo: x, hi: x }
+    }
+
+    // Rounds the bounds of the given interval outward.
+    fn outward(lo: f64, hi: f64) -> Self {
+        Interval { lo: lo.next_down(), hi: hi.next_up() }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    /// Returns true if `other` lies completely inside this interval.
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.lo <= other.lo && other.hi <= self.hi
+    }
+
+    /// Returns the interval widened on both sides by `factor` times its width,
+    /// plus a small absolute margin so degenerate intervals grow as well.
+    pub fn inflate(&self, factor: f64) -> Self {
+        let margin = self.width() * factor + f64::EPSILON;
+        Interval::outward(self.lo - margin, self.hi + margin)
+    }
+
+    /// Returns the interval of x^2 for x in this interval.
+    ///
+    /// This is tighter than `self * self`, which does not know both operands
+    /// are the same number.
+    pub fn sqr(&self) -> Self {
+        let lo2 = self.lo * self.lo;
+        let hi2 = self.hi * self.hi;
+        if self.lo >= 0.0 {
+            Interval::outward(lo2, hi2)
+        } else if self.hi <= 0.0 {
+            Interval::outward(hi2, lo2)
+        } else {
+            Interval { lo: 0.0, hi: lo2.max(hi2).next_up() }
+        }
+    }
+}
+
+impl ops::Add for Interval {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Interval::outward(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+}
+
+impl ops::Sub for Interval {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Interval::outward(self.lo - rhs.hi, self.hi - rhs.lo)
+    }
+}
+
+impl ops::Mul for Interval {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        let products = [self.lo * rhs.lo, self.lo * rhs.hi, self.hi * rhs.lo, self.hi * rhs.hi];
+        let lo = products.iter().cloned().fold(f64::INFINITY, f64::min);
+        let hi = products.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        Interval::outward(lo, hi)
+    }
+}
+
+impl ops::Neg for Interval {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Interval { lo: -self.hi, hi: -self.lo }
+    }
+}
+
+impl Complex<Interval> {
+    /// Returns z^2 using `Interval::sqr` for the tightest bounds.
+    pub fn sqr(&self) -> Self {
+        let re_im = self.re * self.im;
+        Complex::new(self.re.sqr() - self.im.sqr(), re_im + re_im)
+    }
+
+    /// Returns the bounds of |z|^2 using `Interval::sqr`.
+    pub fn norm_sqr_bounds(&self) -> Interval {
+        self.re.sqr() + self.im.sqr()
+    }
+
+    /// Returns true if the box `other` lies completely inside this box.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.re.contains(&other.re) && self.im.contains(&other.im)
+    }
+
+    pub fn inflate(&self, factor: f64) -> Self {
+        Complex::new(self.re.inflate(factor), self.im.inflate(factor))
+    }
+}
+
+impl From<Complex<f64>> for Complex<Interval> {
+    fn from(z: Complex<f64>) -> Self {
+        Complex::new(Interval::point(z.re), Interval::point(z.im))
+    }
+}
+
+/// Treats a cell of the plane as the box of complex numbers it covers.
+impl<T: CoordinateSpace> From<&Cell<T>> for Complex<Interval> {
+    fn from(cell: &Cell<T>) -> Self {
+        Complex::new(Interval::new(cell.min.x, cell.max.x), Interval::new(cell.min.y, cell.max.y))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_enclose_result() {
+        let a = Interval::new(0.1, 0.3);
+        let b = Interval::new(-0.7, 0.2);
+
+        for x in [0.1, 0.2, 0.3] {
+            for y in [-0.7, 0.0, 0.2] {
+                let sum = Interval::point(x + y);
+                let difference = Interval::point(x - y);
+                let product = Interval::point(x * y);
+                assert!((a + b).contains(&sum), "{x} + {y}");
+                assert!((a - b).contains(&difference), "{x} - {y}");
+                assert!((a * b).contains(&product), "{x} * {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_outward_rounding() {
+        let sum = Interval::point(0.1) + Interval::point(0.2);
+        assert!(sum.lo < 0.1 + 0.2);
+        assert!(sum.hi > 0.1 + 0.2);
+    }
+
+    #[test]
+    fn test_sqr() {
+        let x = Interval::new(-1.0, 2.0);
+
+        assert_eq!(0.0, x.sqr().lo);
+        assert!(x.sqr().hi >= 4.0);
+        assert!((x * x).lo <= -2.0);
+        assert!(Interval::new(-3.0, -2.0).sqr().contains(&Interval::new(4.0, 9.0)));
+    }
+
+    #[test]
+    fn test_complex_box() {
+        let z = Complex::new(Interval::new(0.5, 0.6), Interval::new(-0.1, 0.1));
+        let z2 = z.sqr();
+
+        assert!(z2.contains(&Complex::from(Complex::new(0.55, 0.05).square())));
+        assert!(z.inflate(0.1).contains(&z));
+        assert!(!z.contains(&z.inflate(0.1)));
+        assert!(z.norm_sqr_bounds().contains(&Interval::new(0.25, 0.37)));
+    }
+}
@@ -0,0 +1,109 @@
+use slint::Rgba8Pixel;
+
+use crate::fractals::EscapeRecord;
+
+// Height of the surface normal relative to its slope, larger values flatten the relief.
+const RELIEF_HEIGHT: f64 = 1.5;
+
+// Sharpness of the specular highlight.
+const SHININESS: i32 = 20;
+
+/// Shades escaped pixels as a lit surface, which gives the embossed look.
+///
+/// The smooth potential (and the distance estimate, which falls off the same way)
+/// is treated as a height field that rises towards the set. Its slope at a point
+/// runs along z / dz, which gives the surface normal without any neighbouring pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    /// Direction the light comes from in the plane, in degrees counter clockwise from the right.
+    pub angle: f64,
+
+    /// Height of the light above the plane, in degrees from 0 (grazing) to 90 (overhead).
+    pub elevation: f64,
+
+    /// Strength of the white highlight, from 0 to 1.
+    pub specular: f64,
+
+    /// Fraction of the color kept on surfaces facing away from the light, from 0 to 1.
+    pub ambient: f64,
+}
+
+impl Lighting {
+    pub fn new(angle: f64, elevation: f64, specular: f64, ambient: f64) -> Self {
+        Lighting { angle, elevation, specular, ambient }
+    }
+
+    /// Returns the diffuse and specular intensities at an escaped record, or None where
+    /// there is no derivative to find the normal from.
+    pub fn intensity(&self, record: &EscapeRecord) -> Option<(f64, f64)> {
+        let slope = record.z / record.dz;
+        let slope = slope / slope.norm();
+        if !slope.re.is_finite() || !slope.im.is_finite() {
+            return None;
+        }
+
+        let length = (1.0 + RELIEF_HEIGHT * RELIEF_HEIGHT).sqrt();
+        let normal = [slope.re / length, slope.im / length, RELIEF_HEIGHT / length];
+
+        let (angle, elevation) = (self.angle.to_radians(), self.elevation.to_radians());
+        let light = [angle.cos() * elevation.cos(), angle.sin() * elevation.cos(), elevation.sin()];
+        let diffuse = dot(normal, light).max(0.0);
+
+        // Blinn-Phong, with the viewer straight above the plane.
+        let half = [light[0], light[1], light[2] + 1.0];
+        let half_length = dot(half, half).sqrt();
+        let specular = (dot(normal, half) / half_length).max(0.0).powi(SHININESS);
+
+        Some((diffuse, specular))
+    }
+
+    /// Shades the palette color of an escaped record.
+    pub fn shade(&self, color: Rgba8Pixel, record: &EscapeRecord) -> Rgba8Pixel {
+        let Some((diffuse, specular)) = self.intensity(record) else {
+            return color;
+        };
+
+        let brightness = self.ambient + (1.0 - self.ambient) * diffuse;
+        let highlight = 255.0 * self.specular * specular;
+        let channel = |value: u8| (value as f64 * brightness + highlight).clamp(0.0, 255.0).round() as u8;
+        Rgba8Pixel::new(channel(color.r), channel(color.g), channel(color.b), color.a)
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complex::Complex;
+
+    fn record(slope: Complex<f64>) -> EscapeRecord {
+        EscapeRecord { z: slope, dz: Complex::one(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_light_direction() {
+        let lighting = Lighting::new(0.0, 30.0, 0.0, 0.0);
+        let (facing, _) = lighting.intensity(&record(Complex::new(1.0, 0.0))).unwrap();
+        let (away, _) = lighting.intensity(&record(Complex::new(-1.0, 0.0))).unwrap();
+
+        assert!(facing > away, "{facing} <= {away}");
+        assert_eq!(None, lighting.intensity(&EscapeRecord::default()));
+    }
+
+    #[test]
+    fn test_ambient_and_specular() {
+        let grey = Rgba8Pixel::new(100, 100, 100, 255);
+        let away = record(Complex::new(-1.0, 0.0));
+
+        let ambient = Lighting::new(0.0, 0.0, 0.0, 0.5).shade(grey, &away);
+        assert_eq!(Rgba8Pixel::new(50, 50, 50, 255), ambient);
+
+        let dull = Lighting::new(0.0, 60.0, 0.0, 0.5).shade(grey, &record(Complex::new(1.0, 0.0)));
+        let shiny = Lighting::new(0.0, 60.0, 1.0, 0.5).shade(grey, &record(Complex::new(1.0, 0.0)));
+        assert!(shiny.r > dull.r);
+    }
+}
@@ -1,32 +1,154 @@
+// The code generated by slint 1.4 defines its vtables inside functions.
+#![allow(non_local_definitions)]
+
+mod average;
+mod background;
+mod bottcher;
+mod coloring;
+mod complex;
+mod dual;
 mod fractals;
+mod interior;
+mod interval;
+mod lighting;
+mod palette;
+mod palettefile;
+mod palettegen;
 mod point;
+mod render;
+mod simd;
 mod singlecache;
+mod supersample;
+mod symmetry;
+mod texture;
+mod trap;
 mod viewport;
 
-use singlecache::SingleCache;
-use slint::{Image, Rgba8Pixel, SharedPixelBuffer, SharedString};
+use std::{cell::RefCell, fs, path::Path, rc::Rc, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+
+use background::{BackgroundWorker, Job};
+use slint::{Color, Image, ModelRc, Rgba8Pixel, SharedPixelBuffer, SharedString, Timer, TimerMode, VecModel};
 
+use coloring::*;
 use fractals::*;
+use lighting::*;
+use palette::*;
+use palettefile::*;
+use palettegen::*;
 use point::*;
+use render::*;
+use simd::Precision;
+use supersample::*;
+use symmetry::Mirror;
+use texture::*;
+use trap::*;
 use viewport::*;
 
 
-/// Creates the image to be used by the slint image.
+/// Scales of the passes of progressive rendering, as the side of the block of pixels
+/// each computed pixel is shown as. The quality of a pass is its index.
+const PASS_SCALES: [usize; 4] = [8, 4, 2, 1];
+
+/// Quality of the last pass, which computes every pixel.
+const FULL_QUALITY: u32 = PASS_SCALES.len() as u32 - 1;
+
+/// Quality of the pass after the last, which takes the samples of supersampled pixels.
+const SUPERSAMPLED_QUALITY: u32 = FULL_QUALITY + 1;
+
+/// Quality rendered up to while a pane is navigated, so it keeps up with the input.
+const INTERACTIVE_QUALITY: u32 = 1;
+
+/// What the compute stage publishes for a pane: the record of each pixel and, after the
+/// supersampling pass, the samples of the pixels.
+struct PaneRecords {
+    records: Arc<Vec<EscapeRecord>>,
+    samples: Vec<Vec<Sample>>,
+}
+
+/// Computes the raw record of each pixel of the screen, which is the compute stage.
+///
+/// Runs the passes of progressive rendering after that of `latest` up to `quality`,
+/// giving each to `publish` as it finishes, unless `cancel` is set. `strategy` is how the
+/// pixels of each pass are found, and `sampling` which samples the supersampling pass
+/// takes.
 ///
 /// # Arguments
 ///
-/// * `func` - A function that takes an x, y coordinate in usize and returns an rgba pixel color.
-fn create_image(width: u32, height: u32, pixelator: impl Pixelator<Screen>) -> Image {
+/// * `pixelator` - Takes x, y screen coordinates and returns the records of those pixels.
+#[allow(clippy::too_many_arguments)]
+fn compute_records(renderer: &TileRenderer, cancel: &AtomicBool, publish: impl Fn(PaneRecords, u32), latest: Option<(Arc<PaneRecords>, u32)>, quality: u32,
+                   strategy: Strategy, sampling: Sampling, width: u32, height: u32, pixelator: impl Pixelator<Screen, EscapeRecord>) {
+    let (mut coarser, first) = match latest {
+        Some((latest, latest_quality)) => (Some(latest.records.clone()), latest_quality + 1),
+        None => (None, 0),
+    };
+    for pass in first..=quality.min(FULL_QUALITY) {
+        let scale = PASS_SCALES[pass as usize];
+        let coarser_records = coarser.as_deref().map(Vec::as_slice);
+        // Pixels mirroring others are skipped, to be copied once the pass is done.
+        let mirror = Mirror::new(&pixelator, width as usize, height as usize, scale);
+        let records = renderer.render_pass(width as usize, height as usize, scale, coarser_records, strategy, cancel, |x, y| mirror.mirrors(x, y), |positions| {
+            let points: Vec<Point<Screen>> = positions.iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect();
+            pixelator.get_pixels(&points)
+        });
+        let Some(mut records) = records else {
+            return;
+        };
+        mirror.apply(&pixelator, &mut records);
+        let records = Arc::new(records);
+        publish(PaneRecords { records: records.clone(), samples: Vec::new() }, pass);
+        coarser = Some(records);
+    }
+
+    if let (true, Some(records)) = (quality >= SUPERSAMPLED_QUALITY, coarser) {
+        let samples = sampling.sample(renderer, width as usize, height as usize, &records, cancel, |points| {
+            let points: Vec<Point<Screen>> = points.iter().map(|&(x, y)| Point::new(x, y)).collect();
+            pixelator.get_pixels(&points)
+        });
+        if let Some(samples) = samples {
+            publish(PaneRecords { records, samples }, SUPERSAMPLED_QUALITY);
+        }
+    }
+}
+
+/// Computes the records of a pane showing the viewport of a fractal, see `compute_records`.
+/// Certified panes give the fractal the whole cell each pixel covers.
+#[allow(clippy::too_many_arguments)]
+fn compute_pane<T, F>(renderer: &TileRenderer, fractal: &F, viewport: &Viewport<T>, certified: bool, cancel: &AtomicBool, publish: impl Fn(PaneRecords, u32),
+                      latest: Option<(Arc<PaneRecords>, u32)>, quality: u32, strategy: Strategy, sampling: Sampling, width: f32, height: f32)
+where
+    T: CoordinateSpace,
+    F: Pixelator<T, EscapeRecord> + CellPixelator<T, EscapeRecord>,
+{
+    let screen_viewport = Screen::new(width, height);
+    if certified {
+        let pixelator = screen_viewport.decorate_cell_pixelator(viewport, fractal);
+        compute_records(renderer, cancel, publish, latest, quality, strategy, sampling, width as u32, height as u32, pixelator)
+    } else {
+        // Shifted by under a quarter of a pixel, so symmetric halves mirror each other.
+        let (shift_x, shift_y) = Mirror::snap(&screen_viewport.decorate_pixelator(viewport, fractal));
+        let screen_viewport = Viewport::<Screen>::new(-shift_x, -shift_y, width as f64, height as f64);
+        let pixelator = screen_viewport.decorate_pixelator(viewport, fractal);
+        compute_records(renderer, cancel, publish, latest, quality, strategy, sampling, width as u32, height as u32, pixelator)
+    }
+}
+
+/// Colors each record, filtering the samples of supersampled records, which is the
+/// coloring stage. Returns the pixels row by row.
+fn color_records(renderer: &TileRenderer, width: u32, height: u32, records: &PaneRecords, colorer: &Colorer, filter: Filter) -> Vec<Rgba8Pixel> {
     let stride = width as usize;
+    if records.samples.is_empty() {
+        renderer.render(stride, height as usize, |x, y| colorer.color(&records.records[y * stride + x]))
+    } else {
+        filter.resolve(renderer, stride, height as usize, &records.records, &records.samples, |record| colorer.color(record))
+    }
+}
 
+/// Creates the image to be used by the slint image from the colored records.
+fn create_image(renderer: &TileRenderer, width: u32, height: u32, records: &PaneRecords, colorer: &Colorer, filter: Filter) -> Image {
+    let pixels = color_records(renderer, width, height, records, colorer, filter);
     let mut buffer = SharedPixelBuffer::<Rgba8Pixel>::new(width, height);
-    let bytes = buffer.make_mut_slice();
-    for index in 0..bytes.len() {
-        let x = (index % stride) as f64;
-        let y = (index / stride) as f64;
-        let point = Point::<Screen>::new(x, y);
-        bytes[index] = pixelator.get_pixel(&point);
-    }
+    buffer.make_mut_slice().copy_from_slice(&pixels);
     Image::from_rgba8(buffer)
 }
 
@@ -34,10 +156,336 @@ fn create_image(width: u32, height: u32, pixelator: impl Pixelator<Screen>) -> I
 slint::include_modules!();
 
 /// Creates a type safe viewport from a slint view port
-impl<T: CoordinateSpace> Into<Viewport<T>> for SlintViewport {
-    fn into(self) -> Viewport<T> {
-        Viewport::new(self.x1 as f64, self.y1 as f64, self.dx as f64, self.dy as f64)
+impl<T: CoordinateSpace> From<SlintViewport> for Viewport<T> {
+    fn from(slint_viewport: SlintViewport) -> Self {
+        Viewport::new(slint_viewport.x1 as f64, slint_viewport.y1 as f64, slint_viewport.dx as f64, slint_viewport.dy as f64)
+    }
+}
+
+/// Creates the coloring stage for the records of one pane from the slint selections.
+///
+/// `pixel_size` is the width of a pixel in the coordinates of the fractal.
+fn create_colorer(iterations: i32, records: &[EscapeRecord], slint_coloring: &SlintColoring, slint_lighting: SlintLighting,
+                  palette: Palette, pixel_size: f64, texture: Option<Arc<Texture>>) -> Colorer {
+    Colorer::new(iterations, slint_coloring.mode.into(), palette)
+        .with_texture(texture)
+        .with_pixel_size(pixel_size)
+        .with_interior(slint_coloring.interior.into())
+        .with_decomposition(create_decomposition(slint_coloring))
+        .with_lighting(slint_lighting.into())
+        .equalized(records, slint_coloring.equalize as f64)
+}
+
+/// Converts the slint subdivision to the strategy of the renderer.
+impl From<SlintSubdivision> for Strategy {
+    fn from(slint_subdivision: SlintSubdivision) -> Self {
+        match slint_subdivision {
+            SlintSubdivision::Off => Strategy::Every,
+            SlintSubdivision::MarianiSilver => Strategy::Subdivide,
+            SlintSubdivision::Verify => Strategy::Verify,
+        }
+    }
+}
+
+/// Creates the sampling of the compute stage from the slint rendering settings.
+fn create_sampling(slint_rendering: &SlintRendering) -> Sampling {
+    let pattern = if slint_rendering.jittered { Pattern::Jittered } else { Pattern::Grid };
+    let threshold = slint_rendering.adaptive.then_some(slint_rendering.threshold as f64);
+    Sampling::new(slint_rendering.samples.max(1) as usize).with_pattern(pattern).with_adaptive(threshold)
+}
+
+/// Returns how the pixels of a pane are found and which samples are taken. Certified panes
+/// check every pixel, since subdivision fills rectangles from their borders unchecked, and
+/// aren't supersampled, since samples of cells would overlap.
+fn create_strategy(slint_rendering: &SlintRendering, certified: bool) -> (Strategy, Sampling) {
+    if certified {
+        (Strategy::Every, Sampling::new(1))
+    } else {
+        (slint_rendering.subdivision.into(), create_sampling(slint_rendering))
+    }
+}
+
+/// Returns the precision that resolves pixels of the given width, where single precision
+/// is only used when selected.
+fn create_precision(slint_rendering: &SlintRendering, pixel_size: f64) -> Precision {
+    if slint_rendering.single_precision { Precision::for_pixel_size(pixel_size) } else { Precision::Double }
+}
+
+/// Converts the slint reconstruction filter.
+impl From<SlintFilter> for Filter {
+    fn from(slint_filter: SlintFilter) -> Self {
+        match slint_filter {
+            SlintFilter::Box => Filter::Box,
+            SlintFilter::Gaussian => Filter::Gaussian,
+            SlintFilter::Lanczos => Filter::Lanczos,
+        }
+    }
+}
+
+/// Converts the slint orbit trap settings.
+impl From<SlintTrap> for OrbitTrap {
+    fn from(slint_trap: SlintTrap) -> Self {
+        let shape = match slint_trap.shape {
+            SlintTrapShape::Point => TrapShape::Point,
+            SlintTrapShape::Line => TrapShape::Line,
+            SlintTrapShape::Cross => TrapShape::Cross,
+            SlintTrapShape::Circle => TrapShape::Circle,
+            SlintTrapShape::Lattice => TrapShape::Lattice,
+            SlintTrapShape::Image => TrapShape::Image,
+        };
+        let center = complex::Complex::new(slint_trap.x as f64, slint_trap.y as f64);
+        OrbitTrap::new(shape, center, (slint_trap.size as f64).max(1e-6), (slint_trap.rotation as f64).to_radians())
+    }
+}
+
+/// Returns what the kernels need to track along each orbit for the selected coloring.
+fn create_orbit_options(slint_coloring: &SlintColoring, slint_trap: &SlintTrap) -> OrbitOptions {
+    let mode = ColoringMode::from(slint_coloring.mode);
+    let trap_used = mode == ColoringMode::OrbitTrap || slint_coloring.interior == SlintInteriorMode::OrbitTrap;
+    OrbitOptions {
+        average: mode.orbit_average(slint_coloring.stripe_density as f64),
+        trap: trap_used.then(|| slint_trap.clone().into()),
+        bottcher: mode == ColoringMode::BottcherTexture,
+    }
+}
+
+/// Creates the angle decomposition selected in slint.
+fn create_decomposition(slint_coloring: &SlintColoring) -> Decomposition {
+    let sectors = slint_coloring.sectors.max(1) as u32;
+    match slint_coloring.decomposition {
+        SlintDecomposition::Off => Decomposition::Off,
+        SlintDecomposition::Binary => Decomposition::Binary,
+        SlintDecomposition::Sectors => Decomposition::Sectors(sectors),
+        SlintDecomposition::FieldLines => Decomposition::FieldLines(sectors),
+    }
+}
+
+/// Converts the slint coloring mode selection.
+impl From<SlintColoringMode> for ColoringMode {
+    fn from(slint_coloring: SlintColoringMode) -> Self {
+        match slint_coloring {
+            SlintColoringMode::Banded => ColoringMode::Banded,
+            SlintColoringMode::Smooth => ColoringMode::Smooth,
+            SlintColoringMode::Distance => ColoringMode::Distance,
+            SlintColoringMode::Boundary => ColoringMode::Boundary,
+            SlintColoringMode::Stripes => ColoringMode::StripeAverage,
+            SlintColoringMode::Triangle => ColoringMode::TriangleInequality,
+            SlintColoringMode::Curvature => ColoringMode::Curvature,
+            SlintColoringMode::OrbitTrap => ColoringMode::OrbitTrap,
+            SlintColoringMode::Bottcher => ColoringMode::BottcherTexture,
+        }
+    }
+}
+
+/// Converts the slint interior coloring selection.
+impl From<SlintInteriorMode> for InteriorMode {
+    fn from(slint_interior: SlintInteriorMode) -> Self {
+        match slint_interior {
+            SlintInteriorMode::Flat => InteriorMode::Flat,
+            SlintInteriorMode::Period => InteriorMode::Period,
+            SlintInteriorMode::MultiplierAngle => InteriorMode::MultiplierAngle,
+            SlintInteriorMode::MultiplierMagnitude => InteriorMode::MultiplierMagnitude,
+            SlintInteriorMode::AtomDomain => InteriorMode::AtomDomain,
+            SlintInteriorMode::CycleIndex => InteriorMode::CycleIndex,
+            SlintInteriorMode::OrbitTrap => InteriorMode::OrbitTrap,
+        }
+    }
+}
+
+/// Converts the slint lighting settings, where None turns lighting off.
+impl From<SlintLighting> for Option<Lighting> {
+    fn from(slint_lighting: SlintLighting) -> Self {
+        slint_lighting.enabled.then(|| Lighting::new(
+            slint_lighting.angle as f64, slint_lighting.elevation as f64, slint_lighting.specular as f64, slint_lighting.ambient as f64))
+    }
+}
+
+/// Converts the slint interpolation selection, where None keeps the palette's own.
+impl From<SlintInterpolation> for Option<Interpolation> {
+    fn from(slint_interpolation: SlintInterpolation) -> Self {
+        match slint_interpolation {
+            SlintInterpolation::Preset => None,
+            SlintInterpolation::Rgb => Some(Interpolation::Rgb),
+            SlintInterpolation::Hsv => Some(Interpolation::Hsv),
+            SlintInterpolation::Lch => Some(Interpolation::Lch),
+            SlintInterpolation::Oklab => Some(Interpolation::OkLab),
+        }
+    }
+}
+
+/// Converts the slint repeat mode selection.
+impl From<SlintRepeatMode> for RepeatMode {
+    fn from(slint_repeat: SlintRepeatMode) -> Self {
+        match slint_repeat {
+            SlintRepeatMode::Clamp => RepeatMode::Clamp,
+            SlintRepeatMode::Repeat => RepeatMode::Repeat,
+            SlintRepeatMode::Mirror => RepeatMode::Mirror,
+        }
+    }
+}
+
+/// Converts the slint harmony rule selection.
+impl From<SlintHarmony> for Harmony {
+    fn from(slint_harmony: SlintHarmony) -> Self {
+        match slint_harmony {
+            SlintHarmony::Analogous => Harmony::Analogous,
+            SlintHarmony::Triadic => Harmony::Triadic,
+            SlintHarmony::Complementary => Harmony::Complementary,
+        }
+    }
+}
+
+/// Creates the palette selected in slint from the list of known gradients.
+fn create_palette(gradients: &[(String, Gradient)], slint_palette: &SlintPalette) -> Palette {
+    let gradient = gradients.get(slint_palette.index as usize)
+        .map(|(_, gradient)| gradient.clone())
+        .unwrap_or_else(|| Gradient::new(Vec::new(), Interpolation::Rgb));
+    let gradient = match Option::<Interpolation>::from(slint_palette.interpolation) {
+        Some(interpolation) => gradient.with_interpolation(interpolation),
+        None => gradient,
+    };
+    let interior = slint_palette.interior;
+    let interior = Rgba8Pixel::new(interior.red(), interior.green(), interior.blue(), interior.alpha());
+
+    Palette::new(gradient, slint_palette.repeat.into(), slint_palette.offset as f64, slint_palette.density as f64, interior)
+}
+
+/// Directory that palette files are loaded from and exported to.
+const PALETTE_DIRECTORY: &str = "palettes";
+
+/// Loads the built in gradients followed by those of every file in the palette directory.
+///
+/// Returns a status message naming the files that failed to load.
+fn load_gradients(directory: &Path) -> (Vec<(String, Gradient)>, String) {
+    let mut gradients = builtin_gradients();
+    let mut failures = Vec::new();
+
+    // A missing directory just means there are no extra palettes.
+    for (path, result) in read_palette_directory(directory).unwrap_or_default() {
+        match result {
+            Ok(loaded) => gradients.extend(loaded),
+            Err(error) => {
+                println!("Failed to load palette {path}: {error}");
+                failures.push(format!("{path}: {error}"));
+            },
+        }
+    }
+
+    (gradients, failures.join("; "))
+}
+
+/// Handles exporting the selected palette as a GIMP gradient into the palette directory.
+///
+/// Returns a status message.
+fn handle_export_palette(gradients: &[(String, Gradient)], slint_palette: &SlintPalette) -> String {
+    let name = gradients.get(slint_palette.index as usize).map(|(name, _)| name.as_str()).unwrap_or("Palette");
+    let gradient = create_palette(gradients, slint_palette).gradient;
+    let file_name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    let path = Path::new(PALETTE_DIRECTORY).join(format!("{file_name}.ggr"));
+
+    let result = fs::create_dir_all(PALETTE_DIRECTORY).and_then(|_| fs::write(&path, write_ggr(name, &gradient)));
+    match result {
+        Ok(()) => format!("Exported {}", path.display()),
+        Err(error) => format!("Failed to export {}: {error}", path.display()),
+    }
+}
+
+/// Directory that rendered panes are exported to.
+const EXPORT_DIRECTORY: &str = "exports";
+
+/// Handles exporting a pane: renders it at full quality, supersampled as selected, and
+/// saves it as a PNG named after the fractal into the export directory. `colorer` creates
+/// the coloring stage for the records.
+///
+/// Returns a status message, or None when the export was cancelled by a newer one.
+#[allow(clippy::too_many_arguments)]
+fn handle_export_pane<T, F>(name: &str, renderer: &TileRenderer, fractal: &F, viewport: &Viewport<T>, certified: bool, slint_rendering: &SlintRendering,
+                            width: f32, height: f32, cancel: &AtomicBool, colorer: impl FnOnce(&[EscapeRecord]) -> Colorer) -> Option<String>
+where
+    T: CoordinateSpace,
+    F: Pixelator<T, EscapeRecord> + CellPixelator<T, EscapeRecord>,
+{
+    let (strategy, sampling) = create_strategy(slint_rendering, certified);
+    let quality = if sampling.enabled() { SUPERSAMPLED_QUALITY } else { FULL_QUALITY };
+    let rendered = RefCell::new(None);
+    let publish = |records, _| *rendered.borrow_mut() = Some(records);
+    compute_pane(renderer, fractal, viewport, certified, cancel, publish, None, quality, strategy, sampling, width, height);
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    // Not cancelled, so the last pass was published.
+    let records = rendered.into_inner().unwrap();
+
+    let colorer = colorer(&records.records);
+    let pixels = color_records(renderer, width as u32, height as u32, &records, &colorer, slint_rendering.filter.into());
+    let bytes: Vec<u8> = pixels.iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a]).collect();
+    let path = Path::new(EXPORT_DIRECTORY).join(format!("{name}.png"));
+    let result = fs::create_dir_all(EXPORT_DIRECTORY).map_err(|error| error.to_string())
+        .and_then(|_| image::save_buffer(&path, &bytes, width as u32, height as u32, image::ColorType::Rgba8).map_err(|error| error.to_string()));
+    Some(match result {
+        Ok(()) => format!("Exported {}", path.display()),
+        Err(error) => format!("Failed to export {}: {error}", path.display()),
+    })
+}
+
+/// Adds a gradient to the known ones and to the names shown in slint. Returns its index.
+fn add_gradient(gradients: &RefCell<NamedGradients>, names: &VecModel<SharedString>, name: String, gradient: Gradient) -> i32 {
+    names.push(name.as_str().into());
+    let mut gradients = gradients.borrow_mut();
+    gradients.push((name, gradient));
+    gradients.len() as i32 - 1
+}
+
+/// Replaces the gradient of the slint palette with an edited one, returning what the edit
+/// returns besides it, or None when the palette doesn't exist.
+fn edit_gradient<T>(gradients: &RefCell<NamedGradients>, slint_palette: &SlintPalette, edit: impl FnOnce(&Gradient) -> (Gradient, T)) -> Option<T> {
+    let mut gradients = gradients.borrow_mut();
+    let (_, gradient) = gradients.get_mut(slint_palette.index as usize)?;
+    let (edited, result) = edit(gradient);
+    *gradient = edited;
+    Some(result)
+}
+
+/// Handles drawing the gradient of the slint palette, as an image one pixel high.
+fn handle_preview_palette(gradients: &[(String, Gradient)], slint_palette: &SlintPalette, width: f32) -> Image {
+    let gradient = create_palette(gradients, slint_palette).gradient;
+    let width = (width as u32).max(2);
+    let mut buffer = SharedPixelBuffer::<Rgba8Pixel>::new(width, 1);
+    for (x, pixel) in buffer.make_mut_slice().iter_mut().enumerate() {
+        *pixel = gradient.color_at(x as f64 / (width - 1) as f64);
     }
+    Image::from_rgba8(buffer)
+}
+
+/// Handles listing the stops of the slint palette.
+fn handle_palette_stops(gradients: &[(String, Gradient)], slint_palette: &SlintPalette) -> ModelRc<SlintColorStop> {
+    let gradient = create_palette(gradients, slint_palette).gradient;
+    let stops: Vec<SlintColorStop> = gradient.stops().iter().map(|stop| SlintColorStop {
+        position: stop.position as f32,
+        color: Color::from_argb_u8(stop.color.a, stop.color.r, stop.color.g, stop.color.b),
+    }).collect();
+    ModelRc::new(VecModel::from(stops))
+}
+
+/// Handles splitting a color into the channels shown by the palette editor.
+fn handle_color_channels(color: Color) -> SlintRgb {
+    SlintRgb { red: color.red() as i32, green: color.green() as i32, blue: color.blue() as i32 }
+}
+
+/// Extracts the dominant colors of an image file as a gradient, named after the file.
+fn extract_image_gradient(path: &Path, seed: u64) -> Result<(String, Gradient), String> {
+    let texture = Texture::load(path)?;
+    let gradient = extract_gradient(texture.pixels(), EXTRACTED_COLORS, seed)
+        .ok_or_else(|| format!("{} has no opaque pixels", path.display()))?;
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok((format!("{stem} {seed}"), gradient))
+}
+
+/// Returns the loaded texture when it is used, which is by an image orbit trap or the
+/// Böttcher texture mapping.
+fn used_texture(texture: &RefCell<Option<Arc<Texture>>>, slint_coloring: &SlintColoring, slint_trap: &SlintTrap) -> Option<Arc<Texture>> {
+    let used = slint_trap.shape == SlintTrapShape::Image || slint_coloring.mode == SlintColoringMode::Bottcher;
+    used.then(|| texture.borrow().clone()).flatten()
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -61,6 +509,32 @@ fn handle_calculate_julia_constant(slint_viewport: SlintViewport, width: f32, he
 }
 
 
+/// Width in pixels of the outline of the orbit trap drawn while editing it.
+const TRAP_OUTLINE_WIDTH: f64 = 1.5;
+
+/// Color of the outline of the orbit trap.
+const TRAP_OUTLINE_PIXEL: Rgba8Pixel = Rgba8Pixel { r: 0, g: 255, b: 255, a: 255 };
+
+/// Handles drawing the outline of the orbit trap over a pane, on an otherwise transparent image.
+fn handle_trap_overlay(slint_trap: SlintTrap, slint_viewport: SlintViewport, width: f32, height: f32) -> Image {
+    let trap = OrbitTrap::from(slint_trap);
+    let fractal_viewport: Viewport<Julia> = slint_viewport.into();
+    let screen_viewport = Screen::new(width, height);
+    let transformer = screen_viewport.transformer(&fractal_viewport);
+    let outline_width = TRAP_OUTLINE_WIDTH * fractal_viewport.dx.abs() / width as f64 / trap.size;
+
+    let mut buffer = SharedPixelBuffer::<Rgba8Pixel>::new(width as u32, height as u32);
+    let stride = width as usize;
+    for (index, pixel) in buffer.make_mut_slice().iter_mut().enumerate() {
+        let point = Point::<Screen>::new((index % stride) as f64 + 0.5, (index / stride) as f64 + 0.5);
+        let z = complex::Complex::from(&transformer(&point));
+        if trap.outline_distance(z) < outline_width {
+            *pixel = TRAP_OUTLINE_PIXEL;
+        }
+    }
+    Image::from_rgba8(buffer)
+}
+
 /// Handles the scroll wheel function by zooming in or out the viewport.
 /// Returns the resulting viewport.
 ///
@@ -89,44 +563,305 @@ fn handle_open_url(url: SharedString) {
     let _ = webbrowser::open(url.as_str());
 }
 
+/// Time after the last change of a viewport until panes render at full quality again.
+const NAVIGATION_SETTLE: Duration = Duration::from_millis(200);
+
+/// Time between two steps of palette cycling.
+const CYCLING_FRAME: Duration = Duration::from_millis(40);
+
 #[cfg_attr(target_arch = "wasm32",
            wasm_bindgen::prelude::wasm_bindgen(start))]
 pub fn main() {
     let main_window = MainWindow::new().unwrap();
     let logic = main_window.global::<Logic>();
+
+    let (gradients, status) = load_gradients(Path::new(PALETTE_DIRECTORY));
+    let names: Vec<SharedString> = gradients.iter().map(|(name, _)| name.into()).collect();
+    let names = Rc::new(VecModel::from(names));
+    let gradients = Rc::new(RefCell::new(gradients));
+    let palettes = main_window.global::<Palettes>();
+    palettes.set_names(ModelRc::from(names.clone()));
+    palettes.set_status(status.into());
+
+    let export_gradients = gradients.clone();
+    let export_window = main_window.as_weak();
+    palettes.on_export_palette(move |slint_palette| {
+        let status = handle_export_palette(&export_gradients.borrow(), &slint_palette);
+        export_window.unwrap().global::<Palettes>().set_status(status.into());
+    });
+
+    // Generated palettes are added to the list, from where they can be exported.
+    let (generate_gradients, generate_names) = (gradients.clone(), names.clone());
+    let generate_window = main_window.as_weak();
+    palettes.on_generate_palette(move |slint_harmony, seed| {
+        let harmony = Harmony::from(slint_harmony);
+        let gradient = generate_gradient(harmony, seed as u64);
+        let name = format!("{harmony:?} {seed}");
+        generate_window.unwrap().global::<Palettes>().set_status(format!("Generated {name}").into());
+        add_gradient(&generate_gradients, &generate_names, name, gradient)
+    });
+
+    let (extract_gradients, extract_names) = (gradients.clone(), names.clone());
+    let extract_window = main_window.as_weak();
+    palettes.on_extract_palette(move |path, seed, current| {
+        let (status, index) = match extract_image_gradient(Path::new(path.as_str()), seed as u64) {
+            Ok((name, gradient)) => (format!("Extracted {name}"), add_gradient(&extract_gradients, &extract_names, name, gradient)),
+            Err(error) => (error, current),
+        };
+        extract_window.unwrap().global::<Palettes>().set_status(status.into());
+        index
+    });
     
-    let mut mandelbort_cache = SingleCache::new();
-    logic.on_generate_mandelbrot(move | iterations, slint_viewport, width, height | {
+    let preview_gradients = gradients.clone();
+    palettes.on_preview(move |slint_palette, width| handle_preview_palette(&preview_gradients.borrow(), &slint_palette, width));
+    let stops_gradients = gradients.clone();
+    palettes.on_stops(move |slint_palette| handle_palette_stops(&stops_gradients.borrow(), &slint_palette));
+    let nearest_gradients = gradients.clone();
+    palettes.on_nearest_stop(move |slint_palette, position| {
+        let gradients = nearest_gradients.borrow();
+        gradients.get(slint_palette.index as usize).map_or(0, |(_, gradient)| gradient.nearest_stop(position as f64) as i32)
+    });
+    palettes.on_channels(handle_color_channels);
+
+    // The stops are edited in the list of gradients, so edits can be exported.
+    let move_gradients = gradients.clone();
+    palettes.on_move_stop(move |slint_palette, index, position| {
+        edit_gradient(&move_gradients, &slint_palette, |gradient| (gradient.with_stop_position(index as usize, position as f64), ()));
+    });
+    let color_gradients = gradients.clone();
+    palettes.on_color_stop(move |slint_palette, index, color| {
+        let pixel = Rgba8Pixel::new(color.red(), color.green(), color.blue(), color.alpha());
+        edit_gradient(&color_gradients, &slint_palette, |gradient| (gradient.with_stop_color(index as usize, pixel), ()));
+    });
+    let add_gradients = gradients.clone();
+    palettes.on_add_stop(move |slint_palette| {
+        edit_gradient(&add_gradients, &slint_palette, |gradient| gradient.with_stop_added()).map_or(0, |index| index as i32)
+    });
+    let remove_gradients = gradients.clone();
+    palettes.on_remove_stop(move |slint_palette, index| {
+        edit_gradient(&remove_gradients, &slint_palette, |gradient| (gradient.without_stop(index as usize), ()));
+    });
+
+    // Texture of the image orbit trap, and the id given to the slint side for it.
+    let texture: Rc<RefCell<Option<Arc<Texture>>>> = Rc::new(RefCell::new(None));
+    let mut texture_id = 0;
+    let load_texture = texture.clone();
+    let load_window = main_window.as_weak();
+    main_window.global::<Traps>().on_load_texture(move |path| {
+        let status = match Texture::load(Path::new(path.as_str())) {
+            Ok(loaded) => {
+                *load_texture.borrow_mut() = Some(Arc::new(loaded));
+                texture_id += 1;
+                format!("Loaded {path}")
+            },
+            Err(error) => error,
+        };
+        load_window.unwrap().global::<Traps>().set_status(status.into());
+        texture_id
+    });
+
+    // Shared by both panes and their workers, and replaced when the number of threads is changed.
+    let renderer = Rc::new(RefCell::new(Arc::new(TileRenderer::default())));
+    let threads_renderer = renderer.clone();
+    logic.on_set_threads(move |threads| {
+        let renderer = TileRenderer::new(threads.max(0) as usize);
+        println!("--** rendering with {} threads", renderer.threads());
+        *threads_renderer.borrow_mut() = Arc::new(renderer);
+    });
+
+    // Only the compute stage runs in the background, and its latest pass is kept, so
+    // coloring changes redraw without iterating again. Until the first pass is ready the
+    // pane keeps showing the last image.
+    let mut mandelbrot_worker = BackgroundWorker::new();
+    let mut mandelbrot_image = Image::default();
+    let mandelbrot_gradients = gradients.clone();
+    let mandelbrot_texture = texture.clone();
+    let mandelbrot_renderer = renderer.clone();
+    let mandelbrot_window = main_window.as_weak();
+    logic.on_generate_mandelbrot(move | iterations, certified, slint_rendering, slint_coloring, slint_lighting, slint_palette, slint_trap, slint_viewport, width, height, interacting, _frame | {
         let mandelbrot_viewport: Viewport<Mandelbrot> = slint_viewport.into();
-        let screen_viewport = Screen::new(width, height);
-        let key = (iterations, mandelbrot_viewport.clone(), width, height);
+        let options = create_orbit_options(&slint_coloring, &slint_trap);
+        let (strategy, sampling) = create_strategy(&slint_rendering, certified);
+        let interior_checks = slint_rendering.interior_checks;
+        let single_precision = slint_rendering.single_precision;
+        let key = (iterations, certified, strategy, interior_checks, single_precision, sampling, options, mandelbrot_viewport.clone(), width, height);
 
-        mandelbort_cache.get_or_set(key, || {
-            println!("--** on_generate_mandelbrot {width}, {height}, {iterations}, {mandelbrot_viewport:?}");
-            let mandelbrot = Mandelbrot::new(iterations);
-            let pixelator = screen_viewport.decorate_pixelator(&mandelbrot_viewport, &mandelbrot);
-            create_image(width as u32, height as u32, pixelator)
-        })
+        let renderer = mandelbrot_renderer.borrow().clone();
+        let job_viewport = mandelbrot_viewport.clone();
+        let full_quality = if sampling.enabled() { SUPERSAMPLED_QUALITY } else { FULL_QUALITY };
+        let quality = if interacting { INTERACTIVE_QUALITY } else { full_quality };
+        let job_rendering = slint_rendering.clone();
+        let job = move |latest, job: &Job<_, _>| {
+            println!("--** on_generate_mandelbrot {width}, {height}, {iterations}, {certified}, {strategy:?}, {interior_checks}, {sampling:?}, {quality}, {job_viewport:?}");
+            let precision = create_precision(&job_rendering, job_viewport.dx.abs() / width as f64);
+            let mandelbrot = Mandelbrot::new(iterations).with_options(options).with_precision(precision).with_interior_checks(interior_checks);
+            let publish = |records, quality| { job.publish(records, quality); };
+            compute_pane(&renderer, &mandelbrot, &job_viewport, certified, job.cancel(), publish, latest, quality, strategy, sampling, width, height);
+        };
+        let window = mandelbrot_window.clone();
+        let published = move || {
+            let window = window.clone();
+            let _ = slint::invoke_from_event_loop(move || if let Some(window) = window.upgrade() {
+                let renders = window.global::<Renders>();
+                renders.set_mandelbrot_frame(renders.get_mandelbrot_frame() + 1);
+            });
+        };
+
+        if let Some(records) = mandelbrot_worker.get_or_start(key, quality, job, published) {
+            let palette = create_palette(&mandelbrot_gradients.borrow(), &slint_palette);
+            let pixel_size = mandelbrot_viewport.dx.abs() / width as f64;
+            let texture = used_texture(&mandelbrot_texture, &slint_coloring, &slint_trap);
+            let colorer = create_colorer(iterations, &records.records, &slint_coloring, slint_lighting, palette, pixel_size, texture);
+            mandelbrot_image = create_image(&mandelbrot_renderer.borrow(), width as u32, height as u32, &records, &colorer, slint_rendering.filter.into());
+        }
+        mandelbrot_image.clone()
     });
     
-    let mut julia_cache = SingleCache::new();
-    logic.on_generate_julia(move | iterations, c, slint_viewport, width, height | {
+    let mut julia_worker = BackgroundWorker::new();
+    let mut julia_image = Image::default();
+    let julia_gradients = gradients.clone();
+    let julia_texture = texture.clone();
+    let julia_renderer = renderer.clone();
+    let julia_window = main_window.as_weak();
+    logic.on_generate_julia(move | iterations, certified, slint_rendering, slint_coloring, slint_lighting, slint_palette, slint_trap, c, slint_viewport, width, height, interacting, _frame | {
         let julia_viewport: Viewport<Julia> = slint_viewport.into();
-        let screen_viewport = Screen::new(width, height);
-        let key = (iterations, julia_viewport.clone(), c.clone(), width, height);
+        let options = create_orbit_options(&slint_coloring, &slint_trap);
+        let (strategy, sampling) = create_strategy(&slint_rendering, certified);
+        let interior_checks = slint_rendering.interior_checks;
+        let single_precision = slint_rendering.single_precision;
+        let key = (iterations, certified, strategy, interior_checks, single_precision, sampling, options, julia_viewport.clone(), c.clone(), width, height);
+
+        let renderer = julia_renderer.borrow().clone();
+        let job_viewport = julia_viewport.clone();
+        let full_quality = if sampling.enabled() { SUPERSAMPLED_QUALITY } else { FULL_QUALITY };
+        let quality = if interacting { INTERACTIVE_QUALITY } else { full_quality };
+        let job_rendering = slint_rendering.clone();
+        let job = move |latest, job: &Job<_, _>| {
+            println!("--** on_generate_julia {c:?}: {width}, {height}, {iterations}, {certified}, {strategy:?}, {interior_checks}, {sampling:?}, {quality}, {job_viewport:?}");
+            let precision = create_precision(&job_rendering, job_viewport.dx.abs() / width as f64);
+            let julia = Julia::new(iterations, c.real as f64, c.imag as f64).with_options(options).with_precision(precision).with_interior_checks(interior_checks);
+            let publish = |records, quality| { job.publish(records, quality); };
+            compute_pane(&renderer, &julia, &job_viewport, certified, job.cancel(), publish, latest, quality, strategy, sampling, width, height);
+        };
+        let window = julia_window.clone();
+        let published = move || {
+            let window = window.clone();
+            let _ = slint::invoke_from_event_loop(move || if let Some(window) = window.upgrade() {
+                let renders = window.global::<Renders>();
+                renders.set_julia_frame(renders.get_julia_frame() + 1);
+            });
+        };
 
-        julia_cache.get_or_set(key, || {
-            println!("--** on_generate_julia {c:?}: {width}, {height}, {iterations}, {julia_viewport:?}");
-            let julia = Julia::new(iterations, c.real as f64, c.imag as f64);
-            let pixelator = screen_viewport.decorate_pixelator(&julia_viewport, &julia);
-            create_image(width as u32, height as u32, pixelator)
-        })
+        if let Some(records) = julia_worker.get_or_start(key, quality, job, published) {
+            let palette = create_palette(&julia_gradients.borrow(), &slint_palette);
+            let pixel_size = julia_viewport.dx.abs() / width as f64;
+            let texture = used_texture(&julia_texture, &slint_coloring, &slint_trap);
+            let colorer = create_colorer(iterations, &records.records, &slint_coloring, slint_lighting, palette, pixel_size, texture);
+            julia_image = create_image(&julia_renderer.borrow(), width as u32, height as u32, &records, &colorer, slint_rendering.filter.into());
+        }
+        julia_image.clone()
     });
 
+    // Exports render in the background, at full quality whatever the pane shows. Each one
+    // starts a job of its own, cancelling the pane's export in flight.
+    let mut mandelbrot_export_worker = BackgroundWorker::<u32, ()>::new();
+    let mut mandelbrot_exports = 0;
+    let export_gradients = gradients.clone();
+    let export_texture = texture.clone();
+    let export_renderer = renderer.clone();
+    let export_window = main_window.as_weak();
+    logic.on_export_mandelbrot(move | iterations, certified, slint_rendering, slint_coloring, slint_lighting, slint_palette, slint_trap, slint_viewport, width, height | {
+        let viewport: Viewport<Mandelbrot> = slint_viewport.into();
+        let pixel_size = viewport.dx.abs() / width as f64;
+        let options = create_orbit_options(&slint_coloring, &slint_trap);
+        let palette = create_palette(&export_gradients.borrow(), &slint_palette);
+        let texture = used_texture(&export_texture, &slint_coloring, &slint_trap);
+        let renderer = export_renderer.borrow().clone();
+        let window = export_window.clone();
+        let job = move |_, job: &Job<_, _>| {
+            let mandelbrot = Mandelbrot::new(iterations).with_options(options)
+                .with_precision(create_precision(&slint_rendering, pixel_size))
+                .with_interior_checks(slint_rendering.interior_checks);
+            let colorer = |records: &[EscapeRecord]| create_colorer(iterations, records, &slint_coloring, slint_lighting, palette, pixel_size, texture);
+            if let Some(status) = handle_export_pane("mandelbrot", &renderer, &mandelbrot, &viewport, certified, &slint_rendering, width, height, job.cancel(), colorer) {
+                let _ = slint::invoke_from_event_loop(move || if let Some(window) = window.upgrade() {
+                    window.global::<Exports>().set_status(status.into());
+                });
+            }
+        };
+
+        export_window.unwrap().global::<Exports>().set_status("Exporting mandelbrot...".into());
+        mandelbrot_exports += 1;
+        mandelbrot_export_worker.get_or_start(mandelbrot_exports, FULL_QUALITY, job, || {});
+    });
+
+    let mut julia_export_worker = BackgroundWorker::<u32, ()>::new();
+    let mut julia_exports = 0;
+    let export_gradients = gradients.clone();
+    let export_texture = texture.clone();
+    let export_renderer = renderer;
+    let export_window = main_window.as_weak();
+    logic.on_export_julia(move | iterations, certified, slint_rendering, slint_coloring, slint_lighting, slint_palette, slint_trap, c, slint_viewport, width, height | {
+        let viewport: Viewport<Julia> = slint_viewport.into();
+        let pixel_size = viewport.dx.abs() / width as f64;
+        let options = create_orbit_options(&slint_coloring, &slint_trap);
+        let palette = create_palette(&export_gradients.borrow(), &slint_palette);
+        let texture = used_texture(&export_texture, &slint_coloring, &slint_trap);
+        let renderer = export_renderer.borrow().clone();
+        let window = export_window.clone();
+        let job = move |_, job: &Job<_, _>| {
+            let julia = Julia::new(iterations, c.real as f64, c.imag as f64).with_options(options)
+                .with_precision(create_precision(&slint_rendering, pixel_size))
+                .with_interior_checks(slint_rendering.interior_checks);
+            let colorer = |records: &[EscapeRecord]| create_colorer(iterations, records, &slint_coloring, slint_lighting, palette, pixel_size, texture);
+            if let Some(status) = handle_export_pane("julia", &renderer, &julia, &viewport, certified, &slint_rendering, width, height, job.cancel(), colorer) {
+                let _ = slint::invoke_from_event_loop(move || if let Some(window) = window.upgrade() {
+                    window.global::<Exports>().set_status(status.into());
+                });
+            }
+        };
+
+        export_window.unwrap().global::<Exports>().set_status("Exporting julia...".into());
+        julia_exports += 1;
+        julia_export_worker.get_or_start(julia_exports, FULL_QUALITY, job, || {});
+    });
+
+    logic.on_trap_overlay(handle_trap_overlay);
     logic.on_calculate_julia_constant(handle_calculate_julia_constant);
     logic.on_open_url(handle_open_url);
     logic.on_zoom_viewport(handle_zoom_viewport);
 
+    // Every change of a viewport restarts the timer, so panes render coarsely until
+    // navigation settles.
+    let navigation_timer = Timer::default();
+    let navigation_window = main_window.as_weak();
+    main_window.global::<Navigation>().on_navigated(move || {
+        navigation_window.unwrap().global::<Navigation>().set_navigating(true);
+        let settled_window = navigation_window.clone();
+        navigation_timer.start(TimerMode::SingleShot, NAVIGATION_SETTLE, move || {
+            settled_window.unwrap().global::<Navigation>().set_navigating(false);
+        });
+    });
+
+    // Only runs while cycling is playing.
+    let cycling_timer = Timer::default();
+    let cycling_window = main_window.as_weak();
+    main_window.global::<Cycling>().on_toggled(move || {
+        let window = cycling_window.unwrap();
+        if !window.global::<Cycling>().get_playing() {
+            cycling_timer.stop();
+            return;
+        }
+        let timer_window = cycling_window.clone();
+        cycling_timer.start(TimerMode::Repeated, CYCLING_FRAME, move || {
+            let window = timer_window.unwrap();
+            let cycling = window.global::<Cycling>();
+            let direction = if cycling.get_reverse() { -1.0 } else { 1.0 };
+            let shift = direction * cycling.get_speed() * CYCLING_FRAME.as_secs_f32();
+            cycling.invoke_advance(shift.clamp(-0.5, 0.5));
+        });
+    });
+
     let mw = main_window.as_weak();
     logic.on_close(move || { let _ = mw.unwrap().hide(); });
 
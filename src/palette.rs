@@ -0,0 +1,370 @@
+use color::{color_space::D65, Deg, Hsv, Lab, OkLab, Rgb, ToHsv, ToLab, ToOkLab, ToRgb, ToXyz};
+use slint::Rgba8Pixel;
+
+// Number of precomputed colors in a gradient's lookup table.
+const LOOKUP_SIZE: usize = 1024;
+
+/// Color space a gradient interpolates in between its stops.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interpolation {
+    #[default]
+    Rgb,
+
+    /// Hue, saturation and value, taking the shortest way around the hue circle.
+    Hsv,
+
+    /// Lightness, chroma and hue of CIE Lab, taking the shortest way around the hue circle.
+    Lch,
+
+    /// The perceptually uniform OKLab space.
+    OkLab,
+}
+
+/// How coloring values outside of [0, 1] are mapped onto the gradient.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RepeatMode {
+    /// Values outside of [0, 1] take the color of the closest end.
+    #[default]
+    Clamp,
+
+    /// The gradient starts over after each whole number.
+    Repeat,
+
+    /// The gradient runs forwards then backwards, so there is no seam.
+    Mirror,
+}
+
+impl RepeatMode {
+    /// Maps any value into [0, 1].
+    pub fn apply(&self, t: f64) -> f64 {
+        match self {
+            RepeatMode::Clamp => t.clamp(0.0, 1.0),
+            RepeatMode::Repeat => t.rem_euclid(1.0),
+            RepeatMode::Mirror => 1.0 - (t.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+}
+
+/// A color at a position in [0, 1] along a gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    pub position: f64,
+    pub color: Rgba8Pixel,
+}
+
+impl ColorStop {
+    pub fn new(position: f64, color: Rgba8Pixel) -> Self {
+        ColorStop { position, color }
+    }
+}
+
+/// A gradient through any number of color stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    stops: Vec<ColorStop>,
+    interpolation: Interpolation,
+
+    // Colors sampled evenly over [0, 1], since converting between color spaces is
+    // too slow to do for every pixel.
+    lookup: Vec<Rgba8Pixel>,
+}
+
+impl Gradient {
+    /// Creates a gradient from its stops, which don't need to be sorted.
+    ///
+    /// An empty list of stops gives a black to white gradient.
+    pub fn new(mut stops: Vec<ColorStop>, interpolation: Interpolation) -> Self {
+        if stops.is_empty() {
+            stops = vec![ColorStop::new(0.0, Rgba8Pixel::new(0, 0, 0, 255)), ColorStop::new(1.0, Rgba8Pixel::new(255, 255, 255, 255))];
+        }
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        let mut gradient = Gradient { stops, interpolation, lookup: Vec::new() };
+        gradient.lookup = (0..LOOKUP_SIZE)
+            .map(|index| gradient.interpolate(index as f64 / (LOOKUP_SIZE - 1) as f64))
+            .collect();
+        gradient
+    }
+
+    /// Returns the same stops interpolated in a different color space.
+    pub fn with_interpolation(&self, interpolation: Interpolation) -> Self {
+        Gradient::new(self.stops.clone(), interpolation)
+    }
+
+    /// Returns the gradient with a stop moved, but no further than its neighbours, so the
+    /// stops keep their order while it is dragged.
+    pub fn with_stop_position(&self, index: usize, position: f64) -> Self {
+        let mut stops = self.stops.clone();
+        if index < stops.len() {
+            let minimum = if index > 0 { stops[index - 1].position } else { 0.0 };
+            let maximum = stops.get(index + 1).map_or(1.0, |stop| stop.position);
+            stops[index].position = position.clamp(minimum, maximum);
+        }
+        Gradient::new(stops, self.interpolation)
+    }
+
+    /// Returns the gradient with the color of a stop changed.
+    pub fn with_stop_color(&self, index: usize, color: Rgba8Pixel) -> Self {
+        let mut stops = self.stops.clone();
+        if let Some(stop) = stops.get_mut(index) {
+            stop.color = color;
+        }
+        Gradient::new(stops, self.interpolation)
+    }
+
+    /// Returns the gradient with a stop added in the middle of the widest gap between
+    /// stops, with the color already there so the gradient looks the same, and its index.
+    pub fn with_stop_added(&self) -> (Self, usize) {
+        let mut edges = vec![0.0];
+        edges.extend(self.stops.iter().map(|stop| stop.position));
+        edges.push(1.0);
+        let (index, gap) = edges.windows(2).enumerate()
+            .max_by(|a, b| (a.1[1] - a.1[0]).total_cmp(&(b.1[1] - b.1[0])))
+            .unwrap();
+
+        let position = (gap[0] + gap[1]) / 2.0;
+        let mut stops = self.stops.clone();
+        stops.insert(index, ColorStop::new(position, self.interpolate(position)));
+        (Gradient::new(stops, self.interpolation), index)
+    }
+
+    /// Returns the gradient without a stop, unless it is one of the last two.
+    pub fn without_stop(&self, index: usize) -> Self {
+        let mut stops = self.stops.clone();
+        if stops.len() > 2 && index < stops.len() {
+            stops.remove(index);
+        }
+        Gradient::new(stops, self.interpolation)
+    }
+
+    /// Returns the index of the stop closest to a position.
+    pub fn nearest_stop(&self, position: f64) -> usize {
+        self.stops.iter()
+            .map(|stop| (stop.position - position).abs())
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(index, _)| index)
+    }
+
+    /// Returns the stops sorted by position.
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Returns the color at position t in [0, 1] from the lookup table.
+    pub fn color_at(&self, t: f64) -> Rgba8Pixel {
+        let index = (t.clamp(0.0, 1.0) * (LOOKUP_SIZE - 1) as f64).round() as usize;
+        self.lookup[index]
+    }
+
+    /// Calculates the color at position t exactly.
+    pub fn interpolate(&self, t: f64) -> Rgba8Pixel {
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+        if t <= first.position {
+            return first.color;
+        }
+        if t >= last.position {
+            return last.color;
+        }
+
+        // Only reached without a later stop when a position isn't a number.
+        let Some(next) = self.stops.iter().position(|stop| stop.position > t) else {
+            return last.color;
+        };
+        let (from, to) = (self.stops[next - 1], self.stops[next]);
+        let fraction = (t - from.position) / (to.position - from.position);
+        mix(from.color, to.color, fraction as f32, self.interpolation)
+    }
+}
+
+/// Maps coloring values to colors through a gradient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub gradient: Gradient,
+    pub repeat: RepeatMode,
+
+    /// Shifts the gradient along the coloring values.
+    pub offset: f64,
+
+    /// How many times the gradient fits into the range of coloring values.
+    pub density: f64,
+
+    /// Color of points that never escape.
+    pub interior: Rgba8Pixel,
+}
+
+impl Palette {
+    pub fn new(gradient: Gradient, repeat: RepeatMode, offset: f64, density: f64, interior: Rgba8Pixel) -> Self {
+        Palette { gradient, repeat, offset, density, interior }
+    }
+
+    /// Returns the color of a coloring value, where [0, 1] covers the gradient once
+    /// at a density of 1 and no offset.
+    pub fn color(&self, value: f64) -> Rgba8Pixel {
+        let t = self.repeat.apply(value * self.density + self.offset);
+        self.gradient.color_at(t)
+    }
+}
+
+impl Default for Palette {
+    /// Black to white, which matches the original grey iteration count.
+    fn default() -> Self {
+        Palette::new(Gradient::new(Vec::new(), Interpolation::Rgb), RepeatMode::Clamp, 0.0, 1.0, Rgba8Pixel::new(0, 0, 0, 0))
+    }
+}
+
+/// Returns the palettes that are always available, by name.
+pub fn builtin_gradients() -> Vec<(String, Gradient)> {
+    let gradient = |stops: &[(f64, u32)], interpolation| {
+        let stops = stops.iter().map(|&(position, hex)| ColorStop::new(position, hex_pixel(hex))).collect();
+        Gradient::new(stops, interpolation)
+    };
+
+    vec![
+        ("Grey".to_string(), gradient(&[(0.0, 0x000000), (1.0, 0xffffff)], Interpolation::Rgb)),
+        ("Fire".to_string(), gradient(&[(0.0, 0x000000), (0.3, 0x8b0000), (0.6, 0xff8c00), (0.85, 0xffe066), (1.0, 0xffffff)], Interpolation::OkLab)),
+        ("Ocean".to_string(), gradient(&[(0.0, 0x000814), (0.35, 0x003566), (0.7, 0x48cae4), (1.0, 0xf1faee)], Interpolation::Lch)),
+        ("Rainbow".to_string(), gradient(&[(0.0, 0xff0000), (0.5, 0x00ffff), (1.0, 0xff0000)], Interpolation::Hsv)),
+        ("Ultra".to_string(), gradient(&[(0.0, 0x000764), (0.16, 0x206bcb), (0.42, 0xedffff), (0.6425, 0xffaa00), (0.8575, 0x000200), (1.0, 0x000764)], Interpolation::OkLab)),
+    ]
+}
+
+/// Creates an opaque pixel from a 0xRRGGBB value.
+pub fn hex_pixel(hex: u32) -> Rgba8Pixel {
+    Rgba8Pixel::new((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255)
+}
+
+/// Mixes two colors in the given color space, with `fraction` 0 giving `from` and 1 giving `to`.
+pub fn mix(from: Rgba8Pixel, to: Rgba8Pixel, fraction: f32, interpolation: Interpolation) -> Rgba8Pixel {
+    let (a, b) = (to_rgb(from), to_rgb(to));
+    let rgb = match interpolation {
+        Interpolation::Rgb => Rgb::new(lerp(a.r, b.r, fraction), lerp(a.g, b.g, fraction), lerp(a.b, b.b, fraction)),
+        Interpolation::Hsv => {
+            let (a, b): (Hsv<f32>, Hsv<f32>) = (a.to_hsv(), b.to_hsv());
+            let hue = lerp_hue(a.h.0, b.h.0, 360.0, fraction);
+            Hsv::new(Deg(hue), lerp(a.s, b.s, fraction), lerp(a.v, b.v, fraction)).to_rgb()
+        },
+        Interpolation::Lch => {
+            let a: Lab<f32, D65> = a.to_xyz::<f32>().to_lab();
+            let b: Lab<f32, D65> = b.to_xyz::<f32>().to_lab();
+            let lightness = lerp(a.l, b.l, fraction);
+            let chroma = lerp(a.chromacity(), b.chromacity(), fraction);
+            let hue = lerp_hue(a.hue(), b.hue(), std::f32::consts::TAU, fraction);
+            Lab::<f32, D65>::new(lightness, chroma * hue.cos(), chroma * hue.sin()).to_xyz::<f32>().to_rgb()
+        },
+        Interpolation::OkLab => {
+            let (a, b): (OkLab<f32>, OkLab<f32>) = (a.to_oklab(), b.to_oklab());
+            OkLab::new(lerp(a.l, b.l, fraction), lerp(a.a, b.a, fraction), lerp(a.b, b.b, fraction)).to_rgb()
+        },
+    };
+
+    let alpha = lerp(from.a as f32, to.a as f32, fraction).round() as u8;
+    from_rgb(rgb, alpha)
+}
+
+/// Converts the color of a pixel to RGB in [0, 1], dropping its alpha.
+pub fn to_rgb(pixel: Rgba8Pixel) -> Rgb<f32> {
+    Rgb::new(pixel.r as f32 / 255.0, pixel.g as f32 / 255.0, pixel.b as f32 / 255.0)
+}
+
+/// Converts an RGB color in [0, 1] to a pixel, clamping colors out of range.
+pub fn from_rgb(rgb: Rgb<f32>, alpha: u8) -> Rgba8Pixel {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgba8Pixel::new(channel(rgb.r), channel(rgb.g), channel(rgb.b), alpha)
+}
+
+fn lerp(a: f32, b: f32, fraction: f32) -> f32 {
+    a + (b - a) * fraction
+}
+
+// Interpolates an angle the shortest way around a circle of the given size.
+fn lerp_hue(a: f32, b: f32, circle: f32, fraction: f32) -> f32 {
+    let mut difference = (b - a).rem_euclid(circle);
+    if difference > circle / 2.0 {
+        difference -= circle;
+    }
+    (a + difference * fraction).rem_euclid(circle)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba8Pixel = Rgba8Pixel { r: 255, g: 0, b: 0, a: 255 };
+    const BLUE: Rgba8Pixel = Rgba8Pixel { r: 0, g: 0, b: 255, a: 255 };
+
+    #[test]
+    fn test_endpoints_in_every_space() {
+        for interpolation in [Interpolation::Rgb, Interpolation::Hsv, Interpolation::Lch, Interpolation::OkLab] {
+            let gradient = Gradient::new(vec![ColorStop::new(1.0, BLUE), ColorStop::new(0.0, RED)], interpolation);
+
+            assert_eq!(RED, gradient.color_at(0.0), "{interpolation:?}");
+            assert_eq!(BLUE, gradient.color_at(1.0), "{interpolation:?}");
+        }
+    }
+
+    #[test]
+    fn test_hsv_takes_shortest_hue() {
+        // Red to blue the short way passes through magenta, not green.
+        let middle = mix(RED, BLUE, 0.5, Interpolation::Hsv);
+        assert_eq!(Rgba8Pixel::new(255, 0, 255, 255), middle);
+    }
+
+    #[test]
+    fn test_many_stops() {
+        let green = hex_pixel(0x00ff00);
+        let gradient = Gradient::new(vec![ColorStop::new(0.0, RED), ColorStop::new(0.5, green), ColorStop::new(1.0, BLUE)], Interpolation::Rgb);
+
+        assert_eq!(green, gradient.interpolate(0.5));
+        assert_eq!(Rgba8Pixel::new(0, 128, 128, 255), gradient.interpolate(0.75));
+    }
+
+    #[test]
+    fn test_nan_position() {
+        let gradient = Gradient::new(vec![ColorStop::new(0.0, RED), ColorStop::new(f64::NAN, BLUE)], Interpolation::Rgb);
+        assert_eq!(BLUE, gradient.interpolate(0.5));
+    }
+
+    #[test]
+    fn test_repeat_modes() {
+        assert_eq!(1.0, RepeatMode::Clamp.apply(1.5));
+        assert!((RepeatMode::Repeat.apply(1.25) - 0.25).abs() < 1e-12);
+        assert!((RepeatMode::Mirror.apply(1.25) - 0.75).abs() < 1e-12);
+        assert!((RepeatMode::Mirror.apply(-0.25) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_palette_offset_and_density() {
+        let gradient = Gradient::new(vec![ColorStop::new(0.0, RED), ColorStop::new(1.0, BLUE)], Interpolation::Rgb);
+        let palette = Palette::new(gradient, RepeatMode::Repeat, 0.5, 2.0, RED);
+
+        assert_eq!(palette.gradient.color_at(0.5), palette.color(0.0));
+        assert_eq!(palette.gradient.color_at(0.5), palette.color(0.5));
+    }
+
+    #[test]
+    fn test_editing_stops() {
+        let green = hex_pixel(0x00ff00);
+        let gradient = Gradient::new(vec![ColorStop::new(0.0, RED), ColorStop::new(0.25, green), ColorStop::new(1.0, BLUE)], Interpolation::Rgb);
+
+        // Dragging stops at the neighbours.
+        assert_eq!(1.0, gradient.with_stop_position(1, 1.5).stops()[1].position);
+        assert_eq!(0.0, gradient.with_stop_position(1, -0.5).stops()[1].position);
+        assert_eq!(BLUE, gradient.with_stop_color(1, BLUE).stops()[1].color);
+        assert_eq!(2, gradient.nearest_stop(0.7));
+
+        let (added, index) = gradient.with_stop_added();
+        assert_eq!(2, index);
+        assert_eq!(ColorStop::new(0.625, gradient.interpolate(0.625)), added.stops()[2]);
+
+        let removed = gradient.without_stop(1);
+        assert_eq!(2, removed.stops().len());
+        assert_eq!(removed, removed.without_stop(0));
+    }
+}
@@ -0,0 +1,446 @@
+use std::{fmt, fs, io, path::Path};
+
+use slint::Rgba8Pixel;
+
+use crate::palette::{mix, ColorStop, Gradient, Interpolation};
+
+// Number of stops each non-linear GIMP segment is sampled into.
+const GGR_SEGMENT_SAMPLES: usize = 16;
+
+// Ultra Fractal gradients are indexed from 0 to this value, and wrap around.
+const UGR_INDEX_RANGE: f64 = 400.0;
+
+// Number of segments a gradient is sampled into when it can't be exported exactly.
+const EXPORT_SAMPLES: usize = 64;
+
+/// Error from reading or parsing a palette file.
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+
+    /// The file extension is not one of the supported formats.
+    UnknownFormat(String),
+
+    /// The file is malformed at the given (1 based) line.
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(error) => write!(f, "{error}"),
+            PaletteError::UnknownFormat(extension) => write!(f, "unknown palette format '{extension}'"),
+            PaletteError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl From<io::Error> for PaletteError {
+    fn from(error: io::Error) -> Self {
+        PaletteError::Io(error)
+    }
+}
+
+fn parse_error<T>(line: usize, message: impl Into<String>) -> Result<T, PaletteError> {
+    Err(PaletteError::Parse { line, message: message.into() })
+}
+
+/// Reads all the gradients of a palette file, picking the format from its extension.
+///
+/// A Fractint `.map` or GIMP `.ggr` file holds one gradient, an Ultra Fractal `.ugr` file any number.
+/// Gradients are named after the file where the format has no name for them.
+pub fn read_palette_file(path: &Path) -> Result<NamedGradients, PaletteError> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+
+    match extension.as_str() {
+        "map" => Ok(vec![(stem, parse_map(&fs::read_to_string(path)?)?)]),
+        "ggr" => {
+            let (name, gradient) = parse_ggr(&fs::read_to_string(path)?)?;
+            Ok(vec![(if name.is_empty() { stem } else { name }, gradient)])
+        },
+        "ugr" => parse_ugr(&fs::read_to_string(path)?),
+        _ => Err(PaletteError::UnknownFormat(extension)),
+    }
+}
+
+/// Named gradients, as loaded from a palette file.
+pub type NamedGradients = Vec<(String, Gradient)>;
+
+/// Reads the gradients of every palette file in a directory, sorted by file name.
+///
+/// Files that fail to load are returned as errors with their path, so one bad file
+/// doesn't hide the others. Files of other formats are skipped.
+pub fn read_palette_directory(directory: &Path) -> io::Result<Vec<(String, Result<NamedGradients, PaletteError>)>> {
+    let mut paths: Vec<_> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+            matches!(extension.as_str(), "map" | "ggr" | "ugr")
+        })
+        .collect();
+    paths.sort();
+
+    Ok(paths.into_iter().map(|path| (path.display().to_string(), read_palette_file(&path))).collect())
+}
+
+/// Parses a Fractint `.map` file.
+///
+/// Each line holds a red, green and blue value from 0 to 255, optionally followed by a
+/// comment. The colors are spread evenly over the gradient.
+pub fn parse_map(text: &str) -> Result<Gradient, PaletteError> {
+    let mut colors = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut values = line.split_whitespace();
+        let Some(first) = values.next() else {
+            continue;
+        };
+
+        let channel = |value: Option<&str>| -> Result<u8, PaletteError> {
+            match value.map(str::parse::<u8>) {
+                Some(Ok(value)) => Ok(value),
+                Some(Err(_)) => parse_error(index + 1, format!("expected a color value from 0 to 255 in '{line}'")),
+                None => parse_error(index + 1, format!("expected red, green and blue values in '{line}'")),
+            }
+        };
+        let (r, g, b) = (channel(Some(first))?, channel(values.next())?, channel(values.next())?);
+        colors.push(Rgba8Pixel::new(r, g, b, 255));
+    }
+
+    if colors.is_empty() {
+        return parse_error(1, "no colors found");
+    }
+    Ok(Gradient::new(evenly_spaced(&colors), Interpolation::Rgb))
+}
+
+/// Parses a GIMP `.ggr` gradient file, returning its name and gradient.
+///
+/// Each segment has a left, middle and right position, a left and right color, and
+/// a blending function. Segments that aren't linear with a centered middle are sampled
+/// into several stops. Segments blended in HSV take the shortest way around the hue circle.
+pub fn parse_ggr(text: &str) -> Result<(String, Gradient), PaletteError> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+    match lines.next() {
+        Some((_, line)) if line.trim() == "GIMP Gradient" => {},
+        _ => return parse_error(1, "expected 'GIMP Gradient' header"),
+    }
+
+    let mut name = String::new();
+    let (count_line, count) = match lines.next() {
+        Some((index, line)) if line.starts_with("Name:") => {
+            name = line["Name:".len()..].trim().to_string();
+            lines.next().map(|(index, line)| (index, line.trim())).unwrap_or((index, ""))
+        },
+        Some((index, line)) => (index, line.trim()),
+        None => return parse_error(2, "missing segment count"),
+    };
+    let Ok(count) = count.parse::<usize>() else {
+        return parse_error(count_line + 1, format!("expected a segment count, found '{count}'"));
+    };
+
+    let mut stops = Vec::new();
+    for _ in 0..count {
+        let Some((index, line)) = lines.next() else {
+            return parse_error(count_line + 1, format!("expected {count} segments"));
+        };
+        let values: Result<Vec<f64>, _> = line.split_whitespace().map(str::parse::<f64>).collect();
+        let Ok(values) = values else {
+            return parse_error(index + 1, format!("expected numbers in '{line}'"));
+        };
+        if values.len() < 13 {
+            return parse_error(index + 1, format!("expected at least 13 values in a segment, found {}", values.len()));
+        }
+        if values.iter().any(|value| !value.is_finite()) {
+            return parse_error(index + 1, format!("expected finite numbers in '{line}'"));
+        }
+        if !(values[0] <= values[1] && values[1] <= values[2]) {
+            return parse_error(index + 1, format!("expected left <= middle <= right positions in '{line}'"));
+        }
+        stops.extend(ggr_segment_stops(&values));
+    }
+
+    Ok((name, Gradient::new(stops, Interpolation::Rgb)))
+}
+
+// Converts one GIMP segment to stops.
+fn ggr_segment_stops(values: &[f64]) -> Vec<ColorStop> {
+    let (left, middle, right) = (values[0], values[1], values[2]);
+    let color = |offset: usize| {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgba8Pixel::new(channel(values[offset]), channel(values[offset + 1]), channel(values[offset + 2]), channel(values[offset + 3]))
+    };
+    let (left_color, right_color) = (color(3), color(7));
+    let blending = values[11] as i32;
+    let interpolation = if values[12] as i32 == 0 { Interpolation::Rgb } else { Interpolation::Hsv };
+
+    let width = right - left;
+    let middle = if width > 0.0 { ((middle - left) / width).clamp(0.0, 1.0) } else { 0.5 };
+    let linear = blending == 0 && (middle - 0.5).abs() < 1e-6 && interpolation == Interpolation::Rgb;
+    let samples = if linear { 1 } else { GGR_SEGMENT_SAMPLES };
+
+    (0..=samples).map(|sample| {
+        let position = sample as f64 / samples as f64;
+        let factor = ggr_blend(blending, middle, position);
+        ColorStop::new(left + position * width, mix(left_color, right_color, factor as f32, interpolation))
+    }).collect()
+}
+
+// GIMP's blending functions, mapping a position within a segment to a mixing factor.
+fn ggr_blend(blending: i32, middle: f64, position: f64) -> f64 {
+    let linear = if position <= middle {
+        if middle > 0.0 { 0.5 * position / middle } else { 0.5 }
+    } else if middle < 1.0 {
+        0.5 + 0.5 * (position - middle) / (1.0 - middle)
+    } else {
+        1.0
+    };
+
+    match blending {
+        1 => position.powf(0.5f64.ln() / middle.max(1e-10).ln()),
+        2 => ((-std::f64::consts::FRAC_PI_2 + std::f64::consts::PI * linear).sin() + 1.0) / 2.0,
+        3 => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+        4 => 1.0 - (1.0 - linear * linear).sqrt(),
+        5 => if position < middle { 0.0 } else { 1.0 },
+        _ => linear,
+    }
+}
+
+/// Parses an Ultra Fractal `.ugr` gradient file, which may hold several named gradients.
+///
+/// Colors are given at an index from 0 to 399 as decimal 0xBBGGRR values. The gradient
+/// wraps around, so the ends are blended between the last and first colors. Opacity
+/// sections are ignored.
+pub fn parse_ugr(text: &str) -> Result<NamedGradients, PaletteError> {
+    let mut gradients = Vec::new();
+    // Name, starting line and indexed colors of the gradient being read.
+    let mut current: Option<(String, usize, Vec<_>)> = None;
+    let mut in_opacity = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_suffix('{') {
+            if current.is_some() {
+                return parse_error(index + 1, "gradient started before the previous one ended");
+            }
+            current = Some((name.trim().to_string(), index + 1, Vec::new()));
+            in_opacity = false;
+            continue;
+        }
+
+        let Some((name, start_line, colors)) = &mut current else {
+            return parse_error(index + 1, format!("expected a gradient name followed by '{{', found '{line}'"));
+        };
+
+        if line == "}" {
+            if colors.is_empty() {
+                return parse_error(*start_line, format!("gradient '{name}' has no colors"));
+            }
+            gradients.push((name.clone(), Gradient::new(wrap_ugr_colors(colors), Interpolation::Rgb)));
+            current = None;
+        } else if line.starts_with("gradient:") {
+            in_opacity = false;
+        } else if line.starts_with("opacity:") {
+            in_opacity = true;
+        } else if !in_opacity {
+            let mut position = None;
+            for pair in line.split_whitespace() {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                match key {
+                    "index" => match value.parse::<f64>() {
+                        Ok(value) if value.is_finite() => position = Some(value),
+                        _ => return parse_error(index + 1, format!("invalid index '{value}'")),
+                    },
+                    "color" => {
+                        let (Some(position), Ok(bgr)) = (position.take(), value.parse::<u32>()) else {
+                            return parse_error(index + 1, format!("expected 'index=<n> color=<bgr>', found '{pair}'"));
+                        };
+                        colors.push((position, Rgba8Pixel::new(bgr as u8, (bgr >> 8) as u8, (bgr >> 16) as u8, 255)));
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    if let Some((name, start_line, _)) = current {
+        return parse_error(start_line, format!("gradient '{name}' is missing its closing '}}'"));
+    }
+    if gradients.is_empty() {
+        return parse_error(1, "no gradients found");
+    }
+    Ok(gradients)
+}
+
+// Converts indexed Ultra Fractal colors to stops, closing the wrap around at both ends.
+fn wrap_ugr_colors(colors: &mut [(f64, Rgba8Pixel)]) -> Vec<ColorStop> {
+    colors.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first, last) = (colors[0], colors[colors.len() - 1]);
+
+    let gap = first.0 + UGR_INDEX_RANGE - last.0;
+    let fraction = if gap > 0.0 { (UGR_INDEX_RANGE - last.0) / gap } else { 0.0 };
+    let wrap_color = mix(last.1, first.1, fraction as f32, Interpolation::Rgb);
+
+    let mut stops = vec![ColorStop::new(0.0, wrap_color)];
+    stops.extend(colors.iter().map(|&(index, color)| ColorStop::new(index / UGR_INDEX_RANGE, color)));
+    stops.push(ColorStop::new(1.0, wrap_color));
+    stops
+}
+
+/// Writes a gradient as a GIMP `.ggr` file.
+///
+/// RGB gradients become one linear segment per pair of stops. Other interpolations are
+/// sampled into evenly spaced segments so the colors match.
+pub fn write_ggr(name: &str, gradient: &Gradient) -> String {
+    let stops: Vec<ColorStop> = if gradient.interpolation() == Interpolation::Rgb {
+        gradient.stops().to_vec()
+    } else {
+        (0..=EXPORT_SAMPLES).map(|sample| {
+            let position = sample as f64 / EXPORT_SAMPLES as f64;
+            ColorStop::new(position, gradient.interpolate(position))
+        }).collect()
+    };
+
+    // GIMP gradients must cover [0, 1].
+    let mut stops = stops;
+    if stops[0].position > 0.0 {
+        stops.insert(0, ColorStop::new(0.0, stops[0].color));
+    }
+    if stops[stops.len() - 1].position < 1.0 {
+        stops.push(ColorStop::new(1.0, stops[stops.len() - 1].color));
+    }
+
+    let segments: Vec<String> = stops.windows(2)
+        .filter(|pair| pair[1].position > pair[0].position)
+        .map(|pair| {
+            let (left, right) = (pair[0], pair[1]);
+            let color = |c: Rgba8Pixel| format!("{:.6} {:.6} {:.6} {:.6}", c.r as f64 / 255.0, c.g as f64 / 255.0, c.b as f64 / 255.0, c.a as f64 / 255.0);
+            format!("{:.6} {:.6} {:.6} {} {} 0 0",
+                left.position, (left.position + right.position) / 2.0, right.position, color(left.color), color(right.color))
+        })
+        .collect();
+
+    format!("GIMP Gradient\nName: {name}\n{}\n{}\n", segments.len(), segments.join("\n"))
+}
+
+fn evenly_spaced(colors: &[Rgba8Pixel]) -> Vec<ColorStop> {
+    if colors.len() == 1 {
+        return vec![ColorStop::new(0.0, colors[0])];
+    }
+    let last = (colors.len() - 1) as f64;
+    colors.iter().enumerate().map(|(index, &color)| ColorStop::new(index as f64 / last, color)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_map() {
+        let gradient = parse_map("0 0 0 black\n\n255 128 0  ; orange\n255 255 255\n").unwrap();
+
+        assert_eq!(3, gradient.stops().len());
+        assert_eq!(Rgba8Pixel::new(255, 128, 0, 255), gradient.interpolate(0.5));
+    }
+
+    #[test]
+    fn test_parse_map_errors() {
+        assert!(matches!(parse_map(""), Err(PaletteError::Parse { line: 1, .. })));
+        assert!(matches!(parse_map("0 0 0\n0 300 0\n"), Err(PaletteError::Parse { line: 2, .. })));
+        assert!(matches!(parse_map("0 0\n"), Err(PaletteError::Parse { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_parse_ggr() {
+        let text = "GIMP Gradient\nName: Two\n2\n\
+            0.0 0.25 0.5 1 0 0 1 0 1 0 1 0 0\n\
+            0.5 0.75 1.0 0 0 1 1 1 1 1 1 0 0\n";
+        let (name, gradient) = parse_ggr(text).unwrap();
+
+        assert_eq!("Two", name);
+        assert_eq!(Rgba8Pixel::new(255, 0, 0, 255), gradient.interpolate(0.0));
+        assert_eq!(Rgba8Pixel::new(128, 128, 0, 255), gradient.interpolate(0.25));
+        assert_eq!(Rgba8Pixel::new(255, 255, 255, 255), gradient.interpolate(1.0));
+    }
+
+    #[test]
+    fn test_parse_ggr_errors() {
+        assert!(matches!(parse_ggr("Not a gradient\n"), Err(PaletteError::Parse { line: 1, .. })));
+        assert!(matches!(parse_ggr("GIMP Gradient\nName: x\nmany\n"), Err(PaletteError::Parse { line: 3, .. })));
+        assert!(matches!(parse_ggr("GIMP Gradient\nName: x\n1\n0 0.5 1 0 0 0 1\n"), Err(PaletteError::Parse { line: 4, .. })));
+        assert!(matches!(parse_ggr("GIMP Gradient\n1\n0 0.5 nan 0 0 0 1 1 1 1 1 0 0\n"), Err(PaletteError::Parse { line: 3, .. })));
+        assert!(matches!(parse_ggr("GIMP Gradient\n1\n0 inf 1 0 0 0 1 1 1 1 1 0 0\n"), Err(PaletteError::Parse { line: 3, .. })));
+        assert!(matches!(parse_ggr("GIMP Gradient\n1\n1 0.5 0 0 0 0 1 1 1 1 1 0 0\n"), Err(PaletteError::Parse { line: 3, .. })));
+    }
+
+    #[test]
+    fn test_parse_ugr() {
+        let text = "first {\ngradient:\n  title=\"first\" smooth=no\n  index=0 color=255\n  index=200 color=16711680\n\
+            opacity:\n  index=0 opacity=255\n}\n\nsecond {\ngradient:\n  index=100 color=65280\n}\n";
+        let gradients = parse_ugr(text).unwrap();
+
+        assert_eq!(2, gradients.len());
+        assert_eq!("first", gradients[0].0);
+        assert_eq!(Rgba8Pixel::new(255, 0, 0, 255), gradients[0].1.interpolate(0.0));
+        assert_eq!(Rgba8Pixel::new(0, 0, 255, 255), gradients[0].1.interpolate(0.5));
+        assert_eq!(Rgba8Pixel::new(0, 255, 0, 255), gradients[1].1.interpolate(0.9));
+    }
+
+    #[test]
+    fn test_parse_ugr_errors() {
+        assert!(matches!(parse_ugr("index=0 color=0\n"), Err(PaletteError::Parse { line: 1, .. })));
+        assert!(matches!(parse_ugr("a {\ngradient:\n index=0 color=0\n"), Err(PaletteError::Parse { line: 1, .. })));
+        assert!(matches!(parse_ugr("a {\ngradient:\n}\n"), Err(PaletteError::Parse { line: 1, .. })));
+        assert!(matches!(parse_ugr("a {\n index=x color=0\n}\n"), Err(PaletteError::Parse { line: 2, .. })));
+        assert!(matches!(parse_ugr("a {\n index=nan color=0\n}\n"), Err(PaletteError::Parse { line: 2, .. })));
+        assert!(matches!(parse_ugr("a {\n index=-inf color=0\n}\n"), Err(PaletteError::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_ggr_round_trip() {
+        let stops = vec![ColorStop::new(0.0, Rgba8Pixel::new(10, 20, 30, 255)), ColorStop::new(0.3, Rgba8Pixel::new(200, 100, 0, 255)), ColorStop::new(1.0, Rgba8Pixel::new(0, 0, 0, 128))];
+        let gradient = Gradient::new(stops, Interpolation::Rgb);
+        let (name, parsed) = parse_ggr(&write_ggr("Round Trip", &gradient)).unwrap();
+
+        assert_eq!("Round Trip", name);
+        for position in [0.0, 0.15, 0.3, 0.6, 1.0] {
+            assert_eq!(gradient.interpolate(position), parsed.interpolate(position), "at {position}");
+        }
+    }
+
+    #[test]
+    fn test_sample_palettes() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("palettes");
+        for file in ["blues.map", "classic.ugr", "sunset.ggr"] {
+            let path = directory.join(file);
+            assert!(path.exists(), "{file} is missing");
+            let gradients = read_palette_file(&path).unwrap_or_else(|error| panic!("{file}: {error}"));
+            assert!(!gradients.is_empty(), "{file} has no gradients");
+        }
+
+        let files = read_palette_directory(&directory).unwrap();
+        for (path, result) in files {
+            assert!(result.is_ok(), "{path}: {}", result.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_unnamed_ggr() {
+        let path = std::env::temp_dir().join(format!("unnamed-{}.ggr", std::process::id()));
+        fs::write(&path, "GIMP Gradient\n1\n0.0 0.5 1.0 0 0 0 1 1 1 1 1 0 0\n").unwrap();
+        let gradients = read_palette_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(stem, gradients.unwrap()[0].0);
+    }
+}
@@ -0,0 +1,195 @@
+use color::{Deg, Hsv, OkLab, ToOkLab, ToRgb};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use slint::Rgba8Pixel;
+
+use crate::palette::{from_rgb, to_rgb, ColorStop, Gradient, Interpolation};
+
+/// Number of colors pulled out of an image.
+pub const EXTRACTED_COLORS: usize = 6;
+
+// Most pixels an image is reduced to before clustering, since a photo has millions.
+const EXTRACTION_SAMPLES: usize = 4096;
+
+// Most rounds of k-means, which usually settles well before.
+const CLUSTERING_ROUNDS: usize = 32;
+
+/// Color harmony rule that picks the hues of a generated gradient.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Harmony {
+    /// Neighbouring hues, 30° apart.
+    #[default]
+    Analogous,
+
+    /// Three hues evenly spaced around the color wheel.
+    Triadic,
+
+    /// A hue and its opposite.
+    Complementary,
+}
+
+impl Harmony {
+    /// Returns the hues relative to the base hue, in degrees.
+    fn hue_offsets(&self) -> &'static [f32] {
+        match self {
+            Harmony::Analogous => &[-30.0, 0.0, 30.0],
+            Harmony::Triadic => &[0.0, 120.0, 240.0],
+            Harmony::Complementary => &[0.0, 180.0],
+        }
+    }
+}
+
+/// Generates a random gradient from a harmony rule. The same seed gives the same gradient.
+///
+/// The gradient runs from a dark shade of the first hue, through the hues of the rule at
+/// increasing brightness, to a pale tint of the last, so it reads well as iterations grow.
+pub fn generate_gradient(harmony: Harmony, seed: u64) -> Gradient {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let base = rng.gen_range(0.0..360.0);
+    let offsets = harmony.hue_offsets();
+
+    let first = hsv_pixel(base + offsets[0], rng.gen_range(0.6..1.0), rng.gen_range(0.05..0.2));
+    let mut stops = vec![ColorStop::new(0.0, first)];
+    for (index, offset) in offsets.iter().enumerate() {
+        let position = (index + 1) as f64 / (offsets.len() + 1) as f64;
+        let hue = base + offset + rng.gen_range(-10.0..10.0);
+        let value = 0.3 + 0.6 * position as f32 + rng.gen_range(-0.05..0.05);
+        stops.push(ColorStop::new(position, hsv_pixel(hue, rng.gen_range(0.5..0.95), value)));
+    }
+    let last = hsv_pixel(base + offsets[offsets.len() - 1], rng.gen_range(0.05..0.25), rng.gen_range(0.9..1.0));
+    stops.push(ColorStop::new(1.0, last));
+
+    Gradient::new(stops, Interpolation::OkLab)
+}
+
+/// Extracts a gradient of the `count` dominant colors of an image, from dark to light.
+///
+/// The colors are found by k-means clustering in OKLab, so they are perceptually distinct.
+/// The same seed gives the same gradient. Returns None when the image has no opaque pixels.
+pub fn extract_gradient(pixels: &[Rgba8Pixel], count: usize, seed: u64) -> Option<Gradient> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let opaque: Vec<[f32; 3]> = pixels.iter().filter(|pixel| pixel.a >= 128).map(|&pixel| {
+        let oklab: OkLab<f32> = to_rgb(pixel).to_oklab();
+        [oklab.l, oklab.a, oklab.b]
+    }).collect();
+    if opaque.is_empty() || count == 0 {
+        return None;
+    }
+    let samples: Vec<[f32; 3]> = if opaque.len() <= EXTRACTION_SAMPLES {
+        opaque
+    } else {
+        (0..EXTRACTION_SAMPLES).map(|_| opaque[rng.gen_range(0..opaque.len())]).collect()
+    };
+
+    let mut centers = cluster(&samples, count, &mut rng);
+    centers.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    let last = (centers.len() - 1).max(1) as f64;
+    let stops = centers.iter().enumerate().map(|(index, center)| {
+        let rgb = OkLab::new(center[0], center[1], center[2]).to_rgb();
+        ColorStop::new(index as f64 / last, from_rgb(rgb, 255))
+    }).collect();
+    Some(Gradient::new(stops, Interpolation::OkLab))
+}
+
+// Finds up to k cluster centers with k-means, seeded with k-means++. Fewer centers are
+// returned when there are fewer distinct points.
+fn cluster(points: &[[f32; 3]], k: usize, rng: &mut StdRng) -> Vec<[f32; 3]> {
+    let mut centers = vec![points[rng.gen_range(0..points.len())]];
+    while centers.len() < k {
+        // Picks points far from the centers so far more often.
+        let distances: Vec<f32> = points.iter().map(|point| nearest(&centers, point).1).collect();
+        let total: f32 = distances.iter().sum();
+        if total <= 0.0 {
+            break;
+        }
+        let mut target = rng.gen_range(0.0..total);
+        let index = distances.iter().position(|&distance| {
+            target -= distance;
+            target < 0.0
+        }).unwrap_or(points.len() - 1);
+        centers.push(points[index]);
+    }
+
+    for _ in 0..CLUSTERING_ROUNDS {
+        let mut sums = vec![([0.0f32; 3], 0usize); centers.len()];
+        for point in points {
+            let (sum, size) = &mut sums[nearest(&centers, point).0];
+            (0..3).for_each(|axis| sum[axis] += point[axis]);
+            *size += 1;
+        }
+        let moved: Vec<[f32; 3]> = sums.iter().zip(&centers).map(|((sum, size), center)| match size {
+            0 => *center,
+            _ => sum.map(|total| total / *size as f32),
+        }).collect();
+        if moved == centers {
+            break;
+        }
+        centers = moved;
+    }
+    centers
+}
+
+// Returns the index of the center closest to the point and its squared distance.
+fn nearest(centers: &[[f32; 3]], point: &[f32; 3]) -> (usize, f32) {
+    centers.iter()
+        .map(|center| (0..3).map(|axis| (center[axis] - point[axis]).powi(2)).sum::<f32>())
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
+
+fn hsv_pixel(hue: f32, saturation: f32, value: f32) -> Rgba8Pixel {
+    let hsv = Hsv::new(Deg(hue.rem_euclid(360.0)), saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+    from_rgb(hsv.to_rgb(), 255)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_is_seeded() {
+        for harmony in [Harmony::Analogous, Harmony::Triadic, Harmony::Complementary] {
+            let gradient = generate_gradient(harmony, 42);
+
+            assert_eq!(gradient, generate_gradient(harmony, 42), "{harmony:?}");
+            assert_ne!(gradient, generate_gradient(harmony, 43), "{harmony:?}");
+            assert_eq!(harmony.hue_offsets().len() + 2, gradient.stops().len(), "{harmony:?}");
+        }
+    }
+
+    #[test]
+    fn test_generate_dark_to_light() {
+        let gradient = generate_gradient(Harmony::Triadic, 7);
+        let lightness = |pixel: Rgba8Pixel| to_rgb(pixel).to_oklab::<f32>().l;
+
+        let stops = gradient.stops();
+        assert!(lightness(stops[0].color) < lightness(stops[stops.len() - 1].color));
+    }
+
+    #[test]
+    fn test_extract_two_colors() {
+        let red = Rgba8Pixel::new(255, 0, 0, 255);
+        let blue = Rgba8Pixel::new(0, 0, 255, 255);
+        let mut pixels = vec![red; 300];
+        pixels.extend(vec![blue; 100]);
+        let close = |a: Rgba8Pixel, b: Rgba8Pixel| [a.r.abs_diff(b.r), a.g.abs_diff(b.g), a.b.abs_diff(b.b)].iter().all(|&d| d <= 1);
+
+        let gradient = extract_gradient(&pixels, 2, 1).unwrap();
+        let stops = gradient.stops();
+
+        // Blue is the darker of the two.
+        assert_eq!(2, stops.len());
+        assert!(close(blue, stops[0].color), "{:?}", stops[0]);
+        assert!(close(red, stops[1].color), "{:?}", stops[1]);
+        assert_eq!(gradient, extract_gradient(&pixels, 2, 1).unwrap());
+    }
+
+    #[test]
+    fn test_extract_fewer_colors() {
+        let grey = Rgba8Pixel::new(128, 128, 128, 255);
+
+        assert_eq!(1, extract_gradient(&[grey; 10], EXTRACTED_COLORS, 1).unwrap().stops().len());
+        assert_eq!(None, extract_gradient(&[Rgba8Pixel::new(0, 0, 0, 0)], EXTRACTED_COLORS, 1));
+    }
+}
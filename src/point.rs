@@ -14,6 +14,6 @@ pub struct Point<T: CoordinateSpace> {
 
 impl<T: CoordinateSpace> Point<T> {
     pub fn new(x: f64, y: f64) -> Self {
-        Point::<T> { x: x, y: y, _marker: PhantomData }
+        Point::<T> { x, y, _marker: PhantomData }
     }
 }
@@ -0,0 +1,340 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+/// Side of the square tiles that images are split into, small enough that the tiles
+/// spread evenly over the threads, even where some take far more iterations than others.
+pub const TILE_SIZE: usize = 32;
+
+/// How the pixels of each tile are found.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Strategy {
+    /// Every pixel is calculated.
+    #[default]
+    Every,
+
+    /// Mariani-Silver subdivision, where a rectangle whose border pixels all match is
+    /// filled with copies of them without calculating its inside, and any other rectangle
+    /// is split in four. Starts from whole tiles. The filled pixels aren't checked, so
+    /// certified renders don't subdivide.
+    Subdivide,
+
+    /// Subdivides, then calculates every pixel as well and reports how many pixels
+    /// subdivision got wrong, for testing. The calculated pixels are kept.
+    Verify,
+}
+
+/// Pixels that subdivision can fill rectangles with.
+pub trait Fill {
+    /// Whether two pixels are alike enough that a rectangle bordered by such pixels is
+    /// filled with copies of them, such as escape time pixels of the same iteration count.
+    fn matches(&self, other: &Self) -> bool;
+}
+
+/// Renders images in tiles on a work stealing pool of threads.
+///
+/// Falls back to rendering on the calling thread when no threads can be started, as on
+/// the web.
+pub struct TileRenderer {
+    pool: Option<ThreadPool>,
+}
+
+impl TileRenderer {
+    /// Creates a renderer with the given number of threads, where 0 uses one per core.
+    pub fn new(threads: usize) -> Self {
+        TileRenderer { pool: ThreadPoolBuilder::new().num_threads(threads).build().ok() }
+    }
+
+    /// Returns the number of threads rendering.
+    pub fn threads(&self) -> usize {
+        self.pool.as_ref().map_or(1, |pool| pool.current_num_threads())
+    }
+
+    /// Calculates every pixel of an image from its x, y position, returning them row by row.
+    pub fn render<P, F>(&self, width: usize, height: usize, pixel: F) -> Vec<P>
+    where
+        P: Default + Clone + Send,
+        F: Fn(usize, usize) -> P + Sync,
+    {
+        self.render_cancellable(width, height, &AtomicBool::new(false), pixel).unwrap()
+    }
+
+    /// Calculates every pixel of an image like `render`, but gives up and returns None once
+    /// `cancel` is set. Tiles already started are finished first.
+    pub fn render_cancellable<P, F>(&self, width: usize, height: usize, cancel: &AtomicBool, pixel: F) -> Option<Vec<P>>
+    where
+        P: Default + Clone + Send,
+        F: Fn(usize, usize) -> P + Sync,
+    {
+        self.render_batches(width, height, cancel, |positions| positions.iter().map(|&(x, y)| pixel(x, y)).collect())
+    }
+
+    /// Calculates every pixel of an image like `render_cancellable`, but gives `pixels` the
+    /// x, y positions of a whole tile at a time, so it can calculate several together.
+    pub fn render_batches<P, F>(&self, width: usize, height: usize, cancel: &AtomicBool, pixels: F) -> Option<Vec<P>>
+    where
+        P: Default + Clone + Send,
+        F: Fn(&[(usize, usize)]) -> Vec<P> + Sync,
+    {
+        self.render_tiles(width, height, cancel, |tile| pixels(&tile.positions()))
+    }
+
+    // Calculates every pixel of an image a tile at a time, each tile row by row.
+    fn render_tiles<P, F>(&self, width: usize, height: usize, cancel: &AtomicBool, render_tile: F) -> Option<Vec<P>>
+    where
+        P: Default + Clone + Send,
+        F: Fn(&Tile) -> Vec<P> + Sync,
+    {
+        let tiles: Vec<Tile> = (0..height).step_by(TILE_SIZE)
+            .flat_map(|y| (0..width).step_by(TILE_SIZE).map(move |x| Tile::new(x, y, width, height)))
+            .collect();
+        let render_tile = |tile: &Tile| -> Option<Vec<P>> {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            Some(render_tile(tile))
+        };
+
+        let rendered: Vec<Vec<P>> = match &self.pool {
+            Some(pool) => pool.install(|| tiles.par_iter().map(render_tile).collect::<Option<_>>())?,
+            None => tiles.iter().map(render_tile).collect::<Option<_>>()?,
+        };
+
+        let mut image = vec![P::default(); width * height];
+        for (tile, tile_pixels) in tiles.iter().zip(rendered) {
+            let tile_width = tile.x_end - tile.x;
+            for (row, chunk) in tile_pixels.chunks(tile_width).enumerate() {
+                let start = (tile.y + row) * width + tile.x;
+                image[start..start + tile_width].clone_from_slice(chunk);
+            }
+        }
+        Some(image)
+    }
+
+    /// Renders one pass of progressive rendering, where each pixel calculated is shown as a
+    /// block of `scale` by `scale` pixels, from its top left corner. Pixels of `coarser`,
+    /// the pass at twice the scale, are reused, and the rest are given to `pixels` in
+    /// batches like `render_batches`, all of them or those the strategy needs.
+    ///
+    /// Pixels `skipped` are left as defaults for the caller to fill in afterwards, such as
+    /// mirror images of others. Tiles with any are calculated in full rather than
+    /// subdivided, as their defaults would be taken for borders.
+    ///
+    /// Gives up and returns None once `cancel` is set.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_pass<P, F, S>(&self, width: usize, height: usize, scale: usize, coarser: Option<&[P]>, strategy: Strategy, cancel: &AtomicBool, skipped: S, pixels: F) -> Option<Vec<P>>
+    where
+        P: Default + Clone + Send + Sync + Fill,
+        F: Fn(&[(usize, usize)]) -> Vec<P> + Sync,
+        S: Fn(usize, usize) -> bool + Sync,
+    {
+        let (grid_width, grid_height) = (width.div_ceil(scale), height.div_ceil(scale));
+        let reused = |&(grid_x, grid_y): &(usize, usize)| coarser.is_some() && grid_x % 2 == 0 && grid_y % 2 == 0;
+        let skipped = |&(grid_x, grid_y): &(usize, usize)| skipped(grid_x * scale, grid_y * scale);
+        let grid_pixels = |grid_positions: &[(usize, usize)]| -> Vec<P> {
+            let computed_positions: Vec<(usize, usize)> = grid_positions.iter()
+                .filter(|position| !reused(position) && !skipped(position))
+                .map(|&(grid_x, grid_y)| (grid_x * scale, grid_y * scale))
+                .collect();
+            let mut computed = pixels(&computed_positions).into_iter();
+            grid_positions.iter().map(|position| match coarser {
+                Some(coarser) if reused(position) => coarser[position.1 * scale * width + position.0 * scale].clone(),
+                _ if skipped(position) => P::default(),
+                _ => computed.next().unwrap(),
+            }).collect()
+        };
+
+        let missed = AtomicUsize::new(0);
+        let grid = self.render_tiles(grid_width, grid_height, cancel, |tile| {
+            let positions = tile.positions();
+            match strategy {
+                Strategy::Every => grid_pixels(&positions),
+                _ if positions.iter().any(skipped) => grid_pixels(&positions),
+                Strategy::Subdivide => tile.subdivide(grid_pixels),
+                Strategy::Verify => {
+                    let (subdivided, every) = (tile.subdivide(grid_pixels), grid_pixels(&positions));
+                    missed.fetch_add(subdivided.iter().zip(&every).filter(|(a, b)| !a.matches(b)).count(), Ordering::Relaxed);
+                    every
+                },
+            }
+        })?;
+        if strategy == Strategy::Verify {
+            println!("--** subdivision missed {} of {} pixels at scale {scale}", missed.into_inner(), grid_width * grid_height);
+        }
+
+        let image = (0..width * height).map(|index| {
+            let (x, y) = (index % width, index / width);
+            grid[y / scale * grid_width + x / scale].clone()
+        }).collect();
+        Some(image)
+    }
+}
+
+impl Default for TileRenderer {
+    /// One thread per core.
+    fn default() -> Self {
+        TileRenderer::new(0)
+    }
+}
+
+// A rectangle of pixels, from x, y up to but not including x_end, y_end.
+struct Tile {
+    x: usize,
+    y: usize,
+    x_end: usize,
+    y_end: usize,
+}
+
+impl Tile {
+    fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Tile { x, y, x_end: (x + TILE_SIZE).min(width), y_end: (y + TILE_SIZE).min(height) }
+    }
+
+    // Positions of the pixels, row by row.
+    fn positions(&self) -> Vec<(usize, usize)> {
+        (self.y..self.y_end).flat_map(|y| (self.x..self.x_end).map(move |x| (x, y))).collect()
+    }
+
+    // Finds the pixels by Mariani-Silver subdivision, calculating those it can't fill
+    // with `pixels`. The borders of all the rectangles of one level of subdivision are
+    // calculated together, which keeps the batches large.
+    fn subdivide<P: Clone + Fill>(&self, pixels: impl Fn(&[(usize, usize)]) -> Vec<P>) -> Vec<P> {
+        let width = self.x_end - self.x;
+        let mut known: Vec<Option<P>> = vec![None; width * (self.y_end - self.y)];
+        let index = |(x, y): (usize, usize)| y * width + x;
+
+        // Rectangles relative to the tile, from x, y to x_end, y_end including their borders.
+        let mut rectangles = vec![(0, 0, width - 1, self.y_end - self.y - 1)];
+        while !rectangles.is_empty() {
+            let mut wanted = vec![false; known.len()];
+            let mut positions = Vec::new();
+            for &rectangle in &rectangles {
+                for position in border(rectangle) {
+                    if known[index(position)].is_none() && !wanted[index(position)] {
+                        wanted[index(position)] = true;
+                        positions.push((self.x + position.0, self.y + position.1));
+                    }
+                }
+            }
+            for (&(x, y), pixel) in positions.iter().zip(pixels(&positions)) {
+                known[index((x - self.x, y - self.y))] = Some(pixel);
+            }
+
+            let mut smaller = Vec::new();
+            for (x, y, x_end, y_end) in rectangles {
+                // Rectangles this thin are all border.
+                if x_end - x < 2 || y_end - y < 2 {
+                    continue;
+                }
+                let corner = known[index((x, y))].clone().unwrap();
+                if border((x, y, x_end, y_end)).all(|position| known[index(position)].as_ref().unwrap().matches(&corner)) {
+                    for inside_y in y + 1..y_end {
+                        known[index((x + 1, inside_y))..index((x_end, inside_y))].fill(Some(corner.clone()));
+                    }
+                } else {
+                    let (middle_x, middle_y) = ((x + x_end) / 2, (y + y_end) / 2);
+                    smaller.extend([(x, y, middle_x, middle_y), (middle_x, y, x_end, middle_y), (x, middle_y, middle_x, y_end), (middle_x, middle_y, x_end, y_end)]);
+                }
+            }
+            rectangles = smaller;
+        }
+        known.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+// Positions on the border of a rectangle from x, y to x_end, y_end inclusive.
+fn border((x, y, x_end, y_end): (usize, usize, usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    let rows = (x..=x_end).flat_map(move |border_x| [(border_x, y), (border_x, y_end)]);
+    let columns = (y + 1..y_end).flat_map(move |border_y| [(x, border_y), (x_end, border_y)]);
+    rows.chain(columns)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Fill for usize {
+        fn matches(&self, other: &Self) -> bool {
+            self == other
+        }
+    }
+
+    #[test]
+    fn test_render_matches_sequential() {
+        // Sizes that don't divide into whole tiles.
+        let (width, height) = (TILE_SIZE * 2 + 5, TILE_SIZE + 3);
+        let expected: Vec<usize> = (0..width * height).collect();
+
+        for threads in [1, 4] {
+            let renderer = TileRenderer::new(threads);
+            assert_eq!(threads, renderer.threads());
+            assert_eq!(expected, renderer.render(width, height, |x, y| y * width + x));
+        }
+        assert_eq!(Vec::<usize>::new(), TileRenderer::new(2).render(0, 10, |x, _| x));
+    }
+
+    #[test]
+    fn test_render_passes() {
+        let (width, height) = (TILE_SIZE + 5, 7);
+        let renderer = TileRenderer::new(2);
+        let cancel = AtomicBool::new(false);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let pixels = |positions: &[(usize, usize)]| {
+            calls.fetch_add(positions.len(), Ordering::Relaxed);
+            positions.iter().map(|&(x, y)| y * width + x).collect()
+        };
+
+        let coarse = renderer.render_pass(width, height, 4, None, Strategy::Every, &cancel, |_, _| false, pixels).unwrap();
+        assert_eq!(0, coarse[3 * width + 3]);
+        assert_eq!(4 * width + 4, coarse[6 * width + 6]);
+        assert_eq!(10 * 2, calls.swap(0, Ordering::Relaxed));
+
+        let medium = renderer.render_pass(width, height, 2, Some(&coarse), Strategy::Every, &cancel, |_, _| false, pixels).unwrap();
+        assert_eq!(2 * width + 2, medium[3 * width + 3]);
+        assert_eq!(19 * 4 - 10 * 2, calls.swap(0, Ordering::Relaxed));
+
+        // The finest pass matches rendering all at once.
+        let fine = renderer.render_pass(width, height, 1, Some(&medium), Strategy::Every, &cancel, |_, _| false, pixels).unwrap();
+        assert_eq!(renderer.render(width, height, |x, y| y * width + x), fine);
+    }
+
+    #[test]
+    fn test_render_cancelled() {
+        let cancel = AtomicBool::new(true);
+
+        assert_eq!(None, TileRenderer::new(2).render_cancellable(10, 10, &cancel, |x, _| x));
+    }
+
+    #[test]
+    fn test_subdivide() {
+        let (width, height) = (TILE_SIZE * 2 + 5, TILE_SIZE + 3);
+        let renderer = TileRenderer::new(2);
+        let cancel = AtomicBool::new(false);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+
+        // Two bands split by a diagonal, which subdivision finds exactly.
+        let band = |x: usize, y: usize| usize::from(x > y);
+        let pixels = |positions: &[(usize, usize)]| {
+            calls.fetch_add(positions.len(), Ordering::Relaxed);
+            positions.iter().map(|&(x, y)| band(x, y)).collect()
+        };
+        let expected = renderer.render(width, height, band);
+        assert_eq!(expected, renderer.render_pass(width, height, 1, None, Strategy::Subdivide, &cancel, |_, _| false, pixels).unwrap());
+        assert!(calls.load(Ordering::Relaxed) < width * height / 2);
+    }
+
+    #[test]
+    fn test_verify_subdivision() {
+        let (width, height) = (TILE_SIZE, TILE_SIZE);
+        let renderer = TileRenderer::new(2);
+        let cancel = AtomicBool::new(false);
+
+        // A disc inside the tile that no border touches, which subdivision misses.
+        let disc = |x: usize, y: usize| usize::from(x.abs_diff(16).pow(2) + y.abs_diff(16).pow(2) < 25);
+        let pixels = |positions: &[(usize, usize)]| positions.iter().map(|&(x, y)| disc(x, y)).collect();
+        let expected = renderer.render(width, height, disc);
+        assert_ne!(expected, renderer.render_pass(width, height, 1, None, Strategy::Subdivide, &cancel, |_, _| false, pixels).unwrap());
+        assert_eq!(expected, renderer.render_pass(width, height, 1, None, Strategy::Verify, &cancel, |_, _| false, pixels).unwrap());
+    }
+}
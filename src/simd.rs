@@ -0,0 +1,294 @@
+use num_traits::Float;
+
+use crate::{complex::Complex, dual::Dual, fractals::{EscapeRecord, Outcome, ESCAPE_RADIUS2}, interior::{MAX_PERIOD, PERIODICITY_EPSILON}};
+
+/// Number of points iterated together in double precision, which fill a 256 bit register.
+pub const DOUBLE_LANES: usize = 4;
+
+/// Number of points iterated together in single precision.
+pub const SINGLE_LANES: usize = 8;
+
+/// Width of a pixel above which single precision resolves the pixels, with a wide
+/// margin over the rounding of f32 around the sets.
+const SINGLE_PRECISION_PIXEL_SIZE: f64 = 1e-4;
+
+/// Precision that points iterated together are iterated in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Precision {
+    #[default]
+    Double,
+
+    /// Twice as many points at a time, which only resolves pixels far from the deep zooms.
+    /// The iteration counts of points near the boundary can differ from those in double
+    /// precision, so this is only used when asked for.
+    Single,
+}
+
+impl Precision {
+    /// Returns the fastest precision that resolves pixels of the given width.
+    pub fn for_pixel_size(pixel_size: f64) -> Self {
+        if pixel_size > SINGLE_PRECISION_PIXEL_SIZE { Precision::Single } else { Precision::Double }
+    }
+}
+
+/// Which variable the points are in the orbits of z = z^2 + c.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Family {
+    /// The points are c, and z starts at 0.
+    Mandelbrot,
+
+    /// The points are the starting z, with this constant c.
+    Julia(Complex<f64>),
+}
+
+/// Iterates the orbits of the points several at a time, in the lanes of the widest
+/// vectors the processor supports.
+///
+/// Lanes are masked out as their points escape, or are found periodic with
+/// `interior_checks`, and iterated until all are. In double precision the records are the
+/// same as those of `Mandelbrot` and `Julia` with no orbit options, including the
+/// attracting cycles of the bounded points.
+pub fn escape_times(iterations: i32, family: Family, precision: Precision, interior_checks: bool, points: &[Complex<f64>]) -> Vec<EscapeRecord> {
+    match precision {
+        Precision::Double => dispatch::<f64, DOUBLE_LANES>(iterations, family, interior_checks, points),
+        Precision::Single => {
+            let mut records = dispatch::<f32, SINGLE_LANES>(iterations, family, interior_checks, points);
+            // The derivative overflows f32 near the boundary, where the points are iterated
+            // again in double precision rather than giving inf or NaN distances.
+            let overflowed: Vec<usize> = (0..records.len()).filter(|&index| !(records[index].dz.re.is_finite() && records[index].dz.im.is_finite())).collect();
+            if !overflowed.is_empty() {
+                let points: Vec<Complex<f64>> = overflowed.iter().map(|&index| points[index]).collect();
+                let redone = dispatch::<f64, DOUBLE_LANES>(iterations, family, interior_checks, &points);
+                for (index, record) in overflowed.into_iter().zip(redone) {
+                    records[index] = record;
+                }
+            }
+            records
+        },
+    }
+}
+
+// Picks the instruction set at runtime. Without AVX, SSE2 on x86_64 and NEON on aarch64
+// are always there for the compiler to vectorize with. Elsewhere, such as on the web,
+// points are iterated one at a time.
+fn dispatch<T: Float, const N: usize>(iterations: i32, family: Family, interior_checks: bool, points: &[Complex<f64>]) -> Vec<EscapeRecord> {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") {
+        // Safe since the processor was just found to support AVX.
+        return unsafe { escape_times_avx::<T, N>(iterations, family, interior_checks, points) };
+    }
+    if cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+        escape_times_lanes::<T, N>(iterations, family, interior_checks, points)
+    } else {
+        escape_times_lanes::<T, 1>(iterations, family, interior_checks, points)
+    }
+}
+
+// The lanes compiled for AVX, which are only inlined here.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn escape_times_avx<T: Float, const N: usize>(iterations: i32, family: Family, interior_checks: bool, points: &[Complex<f64>]) -> Vec<EscapeRecord> {
+    escape_times_lanes::<T, N>(iterations, family, interior_checks, points)
+}
+
+// Iterates the points N at a time, padding the last chunk with copies of its last point.
+#[inline(always)]
+fn escape_times_lanes<T: Float, const N: usize>(iterations: i32, family: Family, interior_checks: bool, points: &[Complex<f64>]) -> Vec<EscapeRecord> {
+    let mut records = Vec::with_capacity(points.len());
+    for chunk in points.chunks(N) {
+        let mut lanes = [chunk[chunk.len() - 1]; N];
+        lanes[..chunk.len()].copy_from_slice(chunk);
+        let lane_records = iterate_lanes::<T, N>(iterations, family, interior_checks, &lanes);
+        records.extend(lane_records.into_iter().take(chunk.len()));
+    }
+    records
+}
+
+// The kernel, written as loops over the lanes for the compiler to vectorize. It does the
+// same operations in the same order as `escape_time`, so the results round the same.
+#[inline(always)]
+#[allow(clippy::needless_range_loop)]
+fn iterate_lanes<T: Float, const N: usize>(iterations: i32, family: Family, interior_checks: bool, points: &[Complex<f64>; N]) -> [EscapeRecord; N] {
+    let (zero, one) = (T::zero(), T::one());
+    let (mut z_re, mut z_im, mut dz_re, mut dz_im) = ([zero; N], [zero; N], [zero; N], [zero; N]);
+    let (mut c_re, mut c_im) = ([zero; N], [zero; N]);
+    // Derivative of c, which is 1 when differentiating with respect to c.
+    let dc = match family {
+        Family::Mandelbrot => {
+            for lane in 0..N {
+                (c_re[lane], c_im[lane]) = (cast(points[lane].re), cast(points[lane].im));
+            }
+            one
+        },
+        Family::Julia(c) => {
+            for lane in 0..N {
+                (z_re[lane], z_im[lane]) = (cast(points[lane].re), cast(points[lane].im));
+                (c_re[lane], c_im[lane]) = (cast(c.re), cast(c.im));
+                dz_re[lane] = one;
+            }
+            zero
+        },
+    };
+
+    // The dual c of a lane, for the work done on one lane at a time in f64.
+    let dual_c = |lane: usize| match family {
+        Family::Mandelbrot => Dual::new(points[lane], Complex::one()),
+        Family::Julia(c) => Dual::constant(c),
+    };
+
+    let mut done = [false; N];
+    let mut records: [EscapeRecord; N] = std::array::from_fn(|_| EscapeRecord::default());
+    if interior_checks && family == Family::Mandelbrot {
+        for lane in 0..N {
+            if let Some(record) = EscapeRecord::closed_form(points[lane], iterations) {
+                (done[lane], records[lane]) = (true, record);
+            }
+        }
+    }
+
+    let escape_radius2: T = cast(ESCAPE_RADIUS2);
+    let mut min_norm2 = [T::infinity(); N];
+    let mut atom_period = [0; N];
+    // Brent's periodicity checking, as in `PeriodicityChecker`, which saves z on the
+    // same iterations for every lane.
+    let periodicity_epsilon2: T = cast(PERIODICITY_EPSILON * PERIODICITY_EPSILON);
+    let (mut saved_re, mut saved_im) = (z_re, z_im);
+    let (mut saved_at, mut next_save) = (0, 1);
+    for i in 0..=iterations {
+        if done.iter().all(|&done| done) {
+            return records;
+        }
+        let mut escaping = false;
+        for lane in 0..N {
+            escaping |= !done[lane] && z_re[lane] * z_re[lane] + z_im[lane] * z_im[lane] > escape_radius2;
+        }
+        if escaping {
+            for lane in 0..N {
+                if !done[lane] && z_re[lane] * z_re[lane] + z_im[lane] * z_im[lane] > escape_radius2 {
+                    done[lane] = true;
+                    records[lane] = lane_record(Outcome::Escaped, i, (z_re[lane], z_im[lane]), (dz_re[lane], dz_im[lane]), min_norm2[lane], atom_period[lane]);
+                }
+            }
+            if done.iter().all(|&done| done) {
+                return records;
+            }
+        }
+
+        // Finished lanes are iterated along with the others, but their records are kept.
+        for lane in 0..N {
+            let (re, im, d_re, d_im) = (z_re[lane], z_im[lane], dz_re[lane], dz_im[lane]);
+            let (twice_re, twice_im) = (re + re, im + im);
+            z_re[lane] = (re * re - im * im) + c_re[lane];
+            z_im[lane] = (re * im + im * re) + c_im[lane];
+            dz_re[lane] = (twice_re * d_re - twice_im * d_im) + dc;
+            dz_im[lane] = (twice_re * d_im + twice_im * d_re) + zero;
+
+            let norm2 = z_re[lane] * z_re[lane] + z_im[lane] * z_im[lane];
+            let closer = norm2 < min_norm2[lane];
+            min_norm2[lane] = if closer { norm2 } else { min_norm2[lane] };
+            atom_period[lane] = if closer { i + 1 } else { atom_period[lane] };
+        }
+
+        if interior_checks {
+            for lane in 0..N {
+                let (re, im) = (z_re[lane] - saved_re[lane], z_im[lane] - saved_im[lane]);
+                if !done[lane] && re * re + im * im < periodicity_epsilon2 {
+                    done[lane] = true;
+                    records[lane] = lane_record(Outcome::Bounded, iterations, (z_re[lane], z_im[lane]), (dz_re[lane], dz_im[lane]), min_norm2[lane], atom_period[lane])
+                        .with_period(i + 1, i + 1 - saved_at, dual_c(lane));
+                }
+            }
+            if i + 1 == next_save {
+                (saved_re, saved_im, saved_at, next_save) = (z_re, z_im, i + 1, next_save.saturating_mul(2));
+            }
+        }
+    }
+
+    for lane in 0..N {
+        if !done[lane] {
+            records[lane] = lane_record(Outcome::Bounded, iterations, (z_re[lane], z_im[lane]), (dz_re[lane], dz_im[lane]), min_norm2[lane], atom_period[lane])
+                .with_cycle(dual_c(lane).value, iterations.clamp(1, MAX_PERIOD));
+        }
+    }
+    records
+}
+
+#[inline(always)]
+fn cast<T: Float>(value: f64) -> T {
+    T::from(value).unwrap()
+}
+
+// Record of a lane, with nothing tracked along the orbit, like `escape_time` without options.
+#[inline(always)]
+fn lane_record<T: Float>(outcome: Outcome, iterations: i32, z: (T, T), dz: (T, T), min_norm2: T, atom_period: i32) -> EscapeRecord {
+    let to_f64 = |value: T| value.to_f64().unwrap();
+    EscapeRecord {
+        outcome, iterations,
+        z: Complex::new(to_f64(z.0), to_f64(z.1)),
+        dz: Complex::new(to_f64(dz.0), to_f64(dz.1)),
+        min_norm: to_f64(min_norm2).sqrt(), atom_period,
+        ..Default::default()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{fractals::{Julia, Mandelbrot, Pixelator}, CoordinateSpace, Point};
+
+    use super::*;
+
+    // A grid over the sets whose size doesn't divide into whole chunks of lanes.
+    fn grid<T: CoordinateSpace>() -> Vec<Point<T>> {
+        (0..37).flat_map(|y| (0..29).map(move |x| Point::new(-2.0 + x as f64 * 0.1, -1.3 + y as f64 * 0.07))).collect()
+    }
+
+    #[test]
+    fn test_double_matches_scalar() {
+        for interior_checks in [false, true] {
+            let mandelbrot = Mandelbrot::new(300).with_interior_checks(interior_checks);
+            let points = grid::<Mandelbrot>();
+            let complex: Vec<Complex<f64>> = points.iter().map(Complex::from).collect();
+            let expected: Vec<EscapeRecord> = points.iter().map(|point| mandelbrot.get_pixel(point)).collect();
+            assert_eq!(expected, escape_times(300, Family::Mandelbrot, Precision::Double, interior_checks, &complex));
+            assert_eq!(expected, escape_times_lanes::<f64, DOUBLE_LANES>(300, Family::Mandelbrot, interior_checks, &complex));
+            assert_eq!(expected, mandelbrot.get_pixels(&points));
+            assert!(expected.iter().any(|record| record.period > 0));
+            assert!(expected.iter().all(|record| record.min_norm.is_finite()));
+
+            let julia = Julia::new(300, -0.8, 0.156).with_interior_checks(interior_checks);
+            let points = grid::<Julia>();
+            let complex: Vec<Complex<f64>> = points.iter().map(Complex::from).collect();
+            let expected: Vec<EscapeRecord> = points.iter().map(|point| julia.get_pixel(point)).collect();
+            assert_eq!(expected, escape_times(300, Family::Julia(Complex::new(-0.8, 0.156)), Precision::Double, interior_checks, &complex));
+        }
+    }
+
+    #[test]
+    fn test_single_matches_scalar() {
+        let complex: Vec<Complex<f64>> = grid::<Mandelbrot>().iter().map(Complex::from).collect();
+        for family in [Family::Mandelbrot, Family::Julia(Complex::new(0.285, 0.01))] {
+            for interior_checks in [false, true] {
+                let expected = escape_times_lanes::<f32, 1>(200, family, interior_checks, &complex);
+                assert_eq!(expected, escape_times(200, family, Precision::Single, interior_checks, &complex));
+            }
+        }
+        assert_eq!(Vec::<EscapeRecord>::new(), escape_times(200, Family::Mandelbrot, Precision::Single, false, &[]));
+    }
+
+    #[test]
+    fn test_single_derivative_overflow() {
+        // Chaotic orbits on the real axis, whose derivatives outgrow f32 but not f64.
+        let points: Vec<Complex<f64>> = (0..64).map(|index| Complex::new(-1.99 + index as f64 * 0.005, 0.0)).collect();
+        let raw = escape_times_lanes::<f32, 1>(500, Family::Mandelbrot, false, &points);
+        let single = escape_times(500, Family::Mandelbrot, Precision::Single, false, &points);
+        let double = escape_times(500, Family::Mandelbrot, Precision::Double, false, &points);
+        assert!(raw.iter().any(|record| !record.dz.re.is_finite()));
+        for ((raw, single), double) in raw.iter().zip(&single).zip(&double) {
+            assert!(single.dz.re.is_finite() && single.dz.im.is_finite(), "{single:?}");
+            if !raw.dz.re.is_finite() {
+                assert_eq!(double, single);
+            }
+        }
+    }
+}
@@ -7,22 +7,16 @@ pub struct SingleCache<K, V> {
 
 impl<K: PartialEq, V: Clone> SingleCache<K, V> {
     pub fn new() -> Self {
-        return SingleCache { cache_pair: None }
+        SingleCache { cache_pair: None }
     }
 
-    /// Returns the cached value of the given key matches the cached key.
-    /// Otherwise uses the supplied funcation to generate a value, and
-    /// stores that value along with the key.
-    pub fn get_or_set<F>(&mut self, key: K, func: F) -> V where 
-        F: Fn() -> V {
-        if let Some(old_pair) = &self.cache_pair {
-            if key == old_pair.0 {
-                return old_pair.1.clone();
-            }
-        }
+    /// Returns the cached value if the given key matches the cached key.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.cache_pair.as_ref().filter(|(cached_key, _)| cached_key == key).map(|(_, value)| value.clone())
+    }
 
-        let new_value = func();
-        self.cache_pair = Some((key, new_value.clone()));
-        new_value
+    /// Stores the value of a key, replacing the one cached before.
+    pub fn set(&mut self, key: K, value: V) {
+        self.cache_pair = Some((key, value));
     }
 }
@@ -0,0 +1,300 @@
+use std::{f64::consts::PI, sync::atomic::AtomicBool};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use slint::Rgba8Pixel;
+
+use crate::{coloring::smooth_iteration, fractals::{EscapeRecord, Outcome}, render::TileRenderer};
+
+/// Seed of the jittered sample points, fixed so that renders repeat.
+const JITTER_SEED: u64 = 0x5eed;
+
+/// Standard deviation of the Gaussian filter, in pixels.
+const GAUSSIAN_SIGMA: f64 = 0.5;
+
+/// Where the samples of a pixel are taken within it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Pattern {
+    /// The centers of a regular grid of subpixels.
+    #[default]
+    Grid,
+
+    /// A random point in each subpixel, which trades the moiré of regular patterns for noise.
+    Jittered,
+}
+
+/// Which samples of the pixels are computed, which is part of the compute stage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    per_side: usize,
+    pattern: Pattern,
+    adaptive: Option<f64>,
+}
+
+/// A sample of a pixel, at an offset from the pixel's point within half a pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub offset: (f64, f64),
+    pub record: EscapeRecord,
+}
+
+impl Sampling {
+    /// Takes `per_side` by `per_side` samples of each pixel, where 1 turns supersampling off
+    /// and only the pixel's point is computed.
+    pub fn new(per_side: usize) -> Self {
+        Sampling { per_side: per_side.max(1), pattern: Pattern::Grid, adaptive: None }
+    }
+
+    pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Only supersamples pixels whose smooth iteration count differs from that of a
+    /// neighbour by more than the threshold, or that have a neighbour with another outcome
+    /// or period. None supersamples every pixel.
+    pub fn with_adaptive(mut self, threshold: Option<f64>) -> Self {
+        self.adaptive = threshold;
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.per_side > 1
+    }
+
+    /// Computes the samples of every pixel of an image, given the records of the pixels'
+    /// points, where `pixels` computes the records of a batch of points of the screen.
+    /// Pixels that aren't supersampled are left without samples.
+    ///
+    /// Gives up and returns None once `cancel` is set.
+    pub fn sample<F>(&self, renderer: &TileRenderer, width: usize, height: usize, records: &[EscapeRecord], cancel: &AtomicBool, pixels: F) -> Option<Vec<Vec<Sample>>>
+    where
+        F: Fn(&[(f64, f64)]) -> Vec<EscapeRecord> + Sync,
+    {
+        renderer.render_batches(width, height, cancel, |positions| {
+            let offsets: Vec<Vec<(f64, f64)>> = positions.iter().map(|&(x, y)| {
+                if self.wanted(records, width, height, x, y) { self.offsets(y * width + x) } else { Vec::new() }
+            }).collect();
+            let points: Vec<(f64, f64)> = positions.iter().zip(&offsets)
+                .flat_map(|(&(x, y), offsets)| offsets.iter().map(move |(dx, dy)| (x as f64 + dx, y as f64 + dy)))
+                .collect();
+            let mut computed = pixels(&points).into_iter();
+            offsets.into_iter().map(|offsets| {
+                offsets.into_iter().map(|offset| Sample { offset, record: computed.next().unwrap() }).collect()
+            }).collect()
+        })
+    }
+
+    // Offsets of the samples of a pixel, one per subpixel, row by row. Jittered offsets
+    // are seeded by the pixel, so they don't depend on which thread takes them.
+    fn offsets(&self, index: usize) -> Vec<(f64, f64)> {
+        let mut rng = (self.pattern == Pattern::Jittered).then(|| StdRng::seed_from_u64(JITTER_SEED ^ index as u64));
+        let per_side = self.per_side as f64;
+        (0..self.per_side * self.per_side).map(|subpixel| {
+            let (u, v) = match &mut rng {
+                Some(rng) => (rng.gen(), rng.gen()),
+                None => (0.5, 0.5),
+            };
+            let (column, row) = ((subpixel % self.per_side) as f64, (subpixel / self.per_side) as f64);
+            ((column + u) / per_side - 0.5, (row + v) / per_side - 0.5)
+        }).collect()
+    }
+
+    // Whether the pixel at x, y is supersampled.
+    fn wanted(&self, records: &[EscapeRecord], width: usize, height: usize, x: usize, y: usize) -> bool {
+        let Some(threshold) = self.adaptive else {
+            return true;
+        };
+        let record = &records[y * width + x];
+        let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+        neighbours.iter()
+            .filter(|&&(neighbour_x, neighbour_y)| neighbour_x < width && neighbour_y < height)
+            .any(|&(neighbour_x, neighbour_y)| differ(record, &records[neighbour_y * width + neighbour_x], threshold))
+    }
+}
+
+// Whether two records are far enough apart to show an edge between them.
+fn differ(a: &EscapeRecord, b: &EscapeRecord, threshold: f64) -> bool {
+    match (a.outcome, b.outcome) {
+        (Outcome::Escaped, Outcome::Escaped) => (smooth_iteration(a.iterations, a.z) - smooth_iteration(b.iterations, b.z)).abs() > threshold,
+        _ => (a.outcome, a.period) != (b.outcome, b.period),
+    }
+}
+
+/// Reconstruction filter, which weighs the samples around a pixel's point into its color.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Filter {
+    /// Averages the samples within the pixel.
+    #[default]
+    Box,
+
+    /// Weighs the samples up to 1.5 pixels away by a Gaussian, which is softer.
+    Gaussian,
+
+    /// Two lobed Lanczos, up to 2 pixels away, which is sharper, but can ring along edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// Distance from a pixel's point beyond which samples have no weight.
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Gaussian => 1.5,
+            Filter::Lanczos => 2.0,
+        }
+    }
+
+    /// Weight of a sample at distance d along one axis, so that a sample weighs
+    /// weight(dx) weight(dy).
+    pub fn weight(&self, d: f64) -> f64 {
+        match self {
+            Filter::Box => if (-0.5..0.5).contains(&d) { 1.0 } else { 0.0 },
+            Filter::Gaussian => {
+                // Shifted down to reach 0 at the radius.
+                let gaussian = |d: f64| (-d * d / (2.0 * GAUSSIAN_SIGMA * GAUSSIAN_SIGMA)).exp();
+                (gaussian(d) - gaussian(self.radius())).max(0.0)
+            },
+            Filter::Lanczos => {
+                let sinc = |x: f64| if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                if d.abs() < self.radius() { sinc(d) * sinc(d / self.radius()) } else { 0.0 }
+            },
+        }
+    }
+
+    /// Colors the samples of every pixel of an image and filters them into the pixels'
+    /// colors. Pixels without samples stand in with the color of their point.
+    ///
+    /// Each pixel's samples share the weight of its area, so that pixels supersampled by
+    /// the adaptive mode don't outweigh their neighbours with a single sample.
+    ///
+    /// Colors are blended with premultiplied alpha, so transparent samples don't darken
+    /// their neighbours.
+    pub fn resolve<F>(&self, renderer: &TileRenderer, width: usize, height: usize, records: &[EscapeRecord], samples: &[Vec<Sample>], color: F) -> Vec<Rgba8Pixel>
+    where
+        F: Fn(&EscapeRecord) -> Rgba8Pixel + Sync,
+    {
+        let colored: Vec<Vec<((f64, f64), Rgba8Pixel)>> = renderer.render(width, height, |x, y| {
+            let index = y * width + x;
+            if samples[index].is_empty() {
+                vec![((0.0, 0.0), color(&records[index]))]
+            } else {
+                samples[index].iter().map(|sample| (sample.offset, color(&sample.record))).collect()
+            }
+        });
+
+        let reach = self.radius().ceil() as usize;
+        renderer.render(width, height, |x, y| {
+            let (mut sum, mut total) = ([0.0; 4], 0.0);
+            for neighbour_y in y.saturating_sub(reach)..(y + reach + 1).min(height) {
+                for neighbour_x in x.saturating_sub(reach)..(x + reach + 1).min(width) {
+                    let neighbour = &colored[neighbour_y * width + neighbour_x];
+                    let share = 1.0 / neighbour.len() as f64;
+                    for &((dx, dy), pixel) in neighbour {
+                        let weight = share * self.weight(neighbour_x as f64 + dx - x as f64) * self.weight(neighbour_y as f64 + dy - y as f64);
+                        let alpha = pixel.a as f64 / 255.0;
+                        for (sum, channel) in sum.iter_mut().zip([pixel.r, pixel.g, pixel.b]) {
+                            *sum += weight * alpha * channel as f64;
+                        }
+                        sum[3] += weight * alpha;
+                        total += weight;
+                    }
+                }
+            }
+
+            let alpha = if total > 0.0 { sum[3] / total } else { 0.0 };
+            let channel = |sum: f64| if alpha > 0.0 { (sum / total / alpha).clamp(0.0, 255.0).round() as u8 } else { 0 };
+            Rgba8Pixel::new(channel(sum[0]), channel(sum[1]), channel(sum[2]), (alpha * 255.0).clamp(0.0, 255.0).round() as u8)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::Complex;
+
+    use super::*;
+
+    fn escaped(iterations: i32) -> EscapeRecord {
+        EscapeRecord { outcome: Outcome::Escaped, iterations, z: Complex::new(300.0, 0.0), ..Default::default() }
+    }
+
+    #[test]
+    fn test_offsets() {
+        let grid = Sampling::new(2);
+        assert_eq!(vec![(-0.25, -0.25), (0.25, -0.25), (-0.25, 0.25), (0.25, 0.25)], grid.offsets(7));
+
+        let jittered = Sampling::new(3).with_pattern(Pattern::Jittered);
+        let offsets = jittered.offsets(7);
+        assert_eq!(offsets, jittered.offsets(7));
+        assert_ne!(offsets, jittered.offsets(8));
+        for (subpixel, (dx, dy)) in offsets.into_iter().enumerate() {
+            let (column, row) = ((subpixel % 3) as f64, (subpixel / 3) as f64);
+            assert!((column / 3.0 - 0.5..(column + 1.0) / 3.0 - 0.5).contains(&dx), "{dx}");
+            assert!((row / 3.0 - 0.5..(row + 1.0) / 3.0 - 0.5).contains(&dy), "{dy}");
+        }
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        // An edge between the second and third columns.
+        let (width, height) = (4, 2);
+        let records: Vec<EscapeRecord> = (0..width * height).map(|index| escaped(if index % width < 2 { 5 } else { 9 })).collect();
+        let renderer = TileRenderer::new(1);
+        let cancel = AtomicBool::new(false);
+        let pixels = |points: &[(f64, f64)]| points.iter().map(|&(x, _)| escaped(x.round() as i32)).collect();
+
+        let samples = Sampling::new(2).with_adaptive(Some(1.0)).sample(&renderer, width, height, &records, &cancel, pixels).unwrap();
+        let counts: Vec<usize> = samples.iter().map(Vec::len).collect();
+        assert_eq!(vec![0, 4, 4, 0, 0, 4, 4, 0], counts);
+        assert_eq!(escaped(1), samples[1][0].record);
+
+        let samples = Sampling::new(2).sample(&renderer, width, height, &records, &cancel, pixels).unwrap();
+        assert!(samples.iter().all(|samples| samples.len() == 4));
+    }
+
+    #[test]
+    fn test_filters() {
+        for filter in [Filter::Box, Filter::Gaussian, Filter::Lanczos] {
+            assert_eq!(0.0, filter.weight(filter.radius()));
+            assert!(filter.weight(0.0) >= filter.weight(0.4) && filter.weight(0.4) > 0.0);
+        }
+        assert!(Filter::Lanczos.weight(1.5) < 0.0);
+
+        // Half black and half white samples average to grey, while a pixel without
+        // samples keeps its color.
+        let (width, height) = (2, 1);
+        let records = vec![escaped(0), escaped(1)];
+        let samples = vec![(0..4).map(|index| Sample { offset: (0.25 - (index % 2) as f64 * 0.5, 0.0), record: escaped(index % 2) }).collect(), Vec::new()];
+        let color = |record: &EscapeRecord| match record.iterations {
+            0 => Rgba8Pixel::new(0, 0, 0, 255),
+            _ => Rgba8Pixel::new(255, 255, 255, 255),
+        };
+        let pixels = Filter::Box.resolve(&TileRenderer::new(1), width, height, &records, &samples, color);
+        assert_eq!(vec![Rgba8Pixel::new(128, 128, 128, 255), Rgba8Pixel::new(255, 255, 255, 255)], pixels);
+    }
+
+    #[test]
+    fn test_mixed_refinement() {
+        let renderer = TileRenderer::new(1);
+        let color = |record: &EscapeRecord| match record.iterations {
+            0 => Rgba8Pixel::new(0, 0, 0, 255),
+            _ => Rgba8Pixel::new(255, 255, 255, 255),
+        };
+        let refined = |iterations| (0..4).map(|index| Sample { offset: (0.25 - (index % 2) as f64 * 0.5, 0.25 - (index / 2) as f64 * 0.5), record: escaped(iterations) }).collect();
+
+        // A flat region stays flat whichever of its pixels are supersampled.
+        let records = vec![escaped(1); 4];
+        let samples = vec![Vec::new(), refined(1), Vec::new(), refined(1)];
+        for filter in [Filter::Box, Filter::Gaussian, Filter::Lanczos] {
+            assert!(filter.resolve(&renderer, 4, 1, &records, &samples, color).iter().all(|&pixel| pixel == Rgba8Pixel::new(255, 255, 255, 255)));
+        }
+
+        // Next to an edge, a supersampled neighbour weighs about as much as one that isn't,
+        // rather than once per sample.
+        let records = vec![escaped(0), escaped(0), escaped(1), escaped(1)];
+        let unrefined = Filter::Gaussian.resolve(&renderer, 4, 1, &records, &vec![Vec::new(); 4], color);
+        let mixed = Filter::Gaussian.resolve(&renderer, 4, 1, &records, &[Vec::new(), Vec::new(), refined(1), Vec::new()], color);
+        assert!(unrefined[1].r > 0 && (mixed[1].r as i32 - unrefined[1].r as i32).abs() <= 8, "{:?} {:?}", mixed[1], unrefined[1]);
+    }
+}
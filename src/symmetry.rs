@@ -0,0 +1,211 @@
+use crate::{fractals::Pixelator, CoordinateSpace, Point};
+
+/// Distance in pixels within which a mirrored point counts as landing on a pixel.
+const MIRROR_TOLERANCE: f64 = 1e-6;
+
+/// The pixels of one pass of a render that are mirror images of pixels computed earlier in
+/// the same pass, found with the pixelator's `mirror_point`, so that only the other half of
+/// a viewport straddling the axis of symmetry is computed.
+///
+/// Pixels are only mirrored where the image of their point lands on a pixel of the pass,
+/// which the viewport transform decides, so renders off the axis compute every pixel.
+/// Viewports rarely put the axis there exactly, so the points are first shifted by `snap`.
+pub struct Mirror {
+    width: usize,
+    scale: usize,
+
+    // Index in the image of the pixel each pixel of the pass's grid mirrors, if any.
+    sources: Vec<Option<usize>>,
+}
+
+impl Mirror {
+    /// Finds the mirrored pixels of the pass computing every `scale`th pixel of each side.
+    pub fn new<T: CoordinateSpace, P>(pixelator: &dyn Pixelator<T, P>, width: usize, height: usize, scale: usize) -> Self {
+        let grid_width = width.div_ceil(scale);
+        let grid_height = height.div_ceil(scale);
+        let sources = (0..grid_width * grid_height).map(|grid_index| {
+            let (x, y) = (grid_index % grid_width * scale, grid_index / grid_width * scale);
+            let mirrored = pixelator.mirror_point(&Point::new(x as f64, y as f64))?;
+            let (source_x, source_y) = (mirrored.x.round(), mirrored.y.round());
+            let on_pixel = (mirrored.x - source_x).abs() < MIRROR_TOLERANCE && (mirrored.y - source_y).abs() < MIRROR_TOLERANCE;
+            let in_image = (0.0..width as f64).contains(&source_x) && (0.0..height as f64).contains(&source_y);
+            if !on_pixel || !in_image {
+                return None;
+            }
+
+            // Mirroring twice is the identity, so the earlier of the two pixels is computed.
+            let (source_x, source_y) = (source_x as usize, source_y as usize);
+            let earlier = (source_y, source_x) < (y, x);
+            (source_x % scale == 0 && source_y % scale == 0 && earlier).then_some(source_y * width + source_x)
+        }).collect();
+        Mirror { width, scale, sources }
+    }
+
+    /// Returns how far to shift the points of the pixels, by at most a quarter of a pixel
+    /// along each axis, so that the mirror images of the pixels' points land on pixels.
+    pub fn snap<T: CoordinateSpace, P>(pixelator: &dyn Pixelator<T, P>) -> (f64, f64) {
+        // Mirroring a point shifted by s gives the mirror image shifted by -s along the
+        // axes it flips, so shifting by half the mirror image's offset from a pixel evens
+        // it out. Along the axes it doesn't flip the offset is already 0.
+        let Some(mirrored) = pixelator.mirror_point(&Point::new(0.0, 0.0)) else {
+            return (0.0, 0.0);
+        };
+        ((mirrored.x - mirrored.x.round()) / 2.0, (mirrored.y - mirrored.y.round()) / 2.0)
+    }
+
+    /// Whether the pixel of the pass is copied from its mirror image rather than computed.
+    pub fn mirrors(&self, x: usize, y: usize) -> bool {
+        self.source(x, y).is_some()
+    }
+
+    /// Copies the mirrored pixels of the pass's image, filling the block each covers.
+    pub fn apply<T: CoordinateSpace, P: Clone>(&self, pixelator: &dyn Pixelator<T, P>, image: &mut [P]) {
+        let height = image.len() / self.width;
+        for y in (0..height).step_by(self.scale) {
+            for x in (0..self.width).step_by(self.scale) {
+                let Some(source) = self.source(x, y) else {
+                    continue;
+                };
+                let pixel = pixelator.mirror_pixel(image[source].clone());
+                for block_y in y..(y + self.scale).min(height) {
+                    let row = block_y * self.width;
+                    image[row + x..row + (x + self.scale).min(self.width)].fill(pixel.clone());
+                }
+            }
+        }
+    }
+
+    fn source(&self, x: usize, y: usize) -> Option<usize> {
+        let grid_width = self.width.div_ceil(self.scale);
+        self.sources[y / self.scale * grid_width + x / self.scale]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use crate::{fractals::{EscapeRecord, Julia, Mandelbrot, Outcome}, render::{Strategy, TileRenderer, TILE_SIZE}, viewport::Viewport, Screen};
+
+    use super::*;
+
+    fn render<P>(pixelator: &dyn Pixelator<Screen, P>, width: usize, height: usize) -> Vec<P> {
+        let points: Vec<Point<Screen>> = (0..width * height).map(|index| Point::new((index % width) as f64, (index / width) as f64)).collect();
+        pixelator.get_pixels(&points)
+    }
+
+    fn mirrored<P: Clone>(pixelator: &dyn Pixelator<Screen, P>, width: usize, height: usize, scale: usize) -> (usize, Vec<P>) {
+        let mirror = Mirror::new(pixelator, width, height, scale);
+        let mut image = render(pixelator, width, height);
+        let count = mirror.sources.iter().filter(|source| source.is_some()).count();
+        mirror.apply(pixelator, &mut image);
+        (count, image)
+    }
+
+    #[test]
+    fn test_mirrors_mandelbrot() {
+        let (width, height) = (40, 20);
+        let screen = Screen::new(width as f32, height as f32);
+        let mandelbrot = Mandelbrot::new(20);
+        let viewport = Viewport::new(-2.0, 1.0, 2.5, -2.0);
+        let pixelator = screen.decorate_pixelator(&viewport, &mandelbrot);
+
+        // Every row below the axis is mirrored, but for the first, whose image is off screen.
+        let (count, image) = mirrored(&pixelator, width, height, 1);
+        assert_eq!(width * 9, count);
+        let expected = render(&pixelator, width, height);
+        for (record, expected) in image.iter().zip(&expected) {
+            assert_eq!((expected.outcome, expected.iterations, expected.period), (record.outcome, record.iterations, record.period));
+            assert!((record.z - expected.z).norm_sqr() <= 1e-12 * expected.z.norm_sqr().max(1.0), "{record:?} {expected:?}");
+        }
+
+        // On a coarser pass, only rows of the pass mirror each other.
+        let (count, _) = mirrored(&pixelator, width, height, 2);
+        assert_eq!(width / 2 * 4, count);
+    }
+
+    #[test]
+    fn test_mirrors_julia() {
+        let (width, height) = (20, 20);
+        let screen = Screen::new(width as f32, height as f32);
+        let julia = Julia::new(20, -0.8, 0.156);
+        let viewport = Viewport::new(-1.5, 1.5, 3.0, -3.0);
+        let pixelator = screen.decorate_pixelator(&viewport, &julia);
+
+        // Pixels after the center mirror those before it, but for the first row and column,
+        // whose images are off screen.
+        let (count, image) = mirrored(&pixelator, width, height, 1);
+        assert_eq!(((width - 1) * (height - 1) - 1) / 2, count);
+        let expected = render(&pixelator, width, height);
+        for (record, expected) in image.iter().zip(&expected) {
+            assert_eq!((expected.outcome, expected.iterations), (record.outcome, record.iterations));
+            assert!((record.dz - expected.dz).norm_sqr() <= 1e-12 * expected.dz.norm_sqr().max(1.0), "{record:?} {expected:?}");
+        }
+        assert!(image.iter().any(|record| record.outcome == Outcome::Escaped));
+    }
+
+    #[test]
+    fn test_off_axis() {
+        let screen = Screen::new(10.0, 10.0);
+        let viewport = Viewport::new(-1.0, 1.3, 0.5, -0.5);
+        let mandelbrot = Mandelbrot::new(20);
+        let pixelator = screen.decorate_pixelator(&viewport, &mandelbrot);
+        assert_eq!(0, mirrored(&pixelator, 10, 10, 1).0);
+    }
+
+    // Rings about a point on the axis of symmetry y = 40, which subdivision finds exactly.
+    struct Rings;
+
+    impl Pixelator<Screen, EscapeRecord> for Rings {
+        fn get_pixel(&self, point: &Point<Screen>) -> EscapeRecord {
+            let distance = ((point.x - 20.0).powi(2) + (point.y - 40.0).powi(2)).sqrt();
+            EscapeRecord { outcome: Outcome::Escaped, iterations: 1 + (distance / 12.0) as i32, ..Default::default() }
+        }
+
+        fn mirror_point(&self, point: &Point<Screen>) -> Option<Point<Screen>> {
+            Some(Point::new(point.x, 80.0 - point.y))
+        }
+    }
+
+    #[test]
+    fn test_subdivide_mirrored() {
+        let (width, height) = (TILE_SIZE * 2, 81);
+        let renderer = TileRenderer::new(2);
+        let cancel = AtomicBool::new(false);
+        let pixels = |positions: &[(usize, usize)]| {
+            let points: Vec<Point<Screen>> = positions.iter().map(|&(x, y)| Point::new(x as f64, y as f64)).collect();
+            Rings.get_pixels(&points)
+        };
+
+        let every = renderer.render_pass(width, height, 1, None, Strategy::Every, &cancel, |_, _| false, pixels).unwrap();
+        for strategy in [Strategy::Subdivide, Strategy::Verify] {
+            let mirror = Mirror::new(&Rings, width, height, 1);
+            let mut image = renderer.render_pass(width, height, 1, None, strategy, &cancel, |x, y| mirror.mirrors(x, y), pixels).unwrap();
+            mirror.apply(&Rings, &mut image);
+            assert_eq!(every, image);
+        }
+    }
+
+    #[test]
+    fn test_snap() {
+        // A viewport panned off the pixel grid, as it comes from slint in f32.
+        let (width, height) = (40, 30);
+        let viewport = Viewport::<Mandelbrot>::new(-2.1234567_f32 as f64, 0.9876543_f32 as f64, 2.5_f32 as f64, -1.8765_f32 as f64);
+        let mandelbrot = Mandelbrot::new(20);
+        let screen = Screen::new(width as f32, height as f32);
+        let pixelator = screen.decorate_pixelator(&viewport, &mandelbrot);
+        assert_eq!(0, mirrored(&pixelator, width, height, 1).0);
+
+        let (shift_x, shift_y) = Mirror::snap(&pixelator);
+        assert!(shift_x.abs() < 1e-9 && shift_y.abs() <= 0.25 && shift_y != 0.0);
+        let shifted = Viewport::<Screen>::new(-shift_x, -shift_y, width as f64, height as f64);
+        let pixelator = shifted.decorate_pixelator(&viewport, &mandelbrot);
+        let (count, image) = mirrored(&pixelator, width, height, 1);
+        assert!(count >= width * 5, "{count}");
+        let expected = render(&pixelator, width, height);
+        for (record, expected) in image.iter().zip(&expected) {
+            assert_eq!((expected.outcome, expected.iterations), (record.outcome, record.iterations));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use slint::Rgba8Pixel;
+
+/// An image that can be sampled at any position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgba8Pixel>,
+}
+
+impl Texture {
+    /// Creates a texture from rows of pixels. Returns None if the size doesn't match.
+    pub fn new(width: usize, height: usize, pixels: Vec<Rgba8Pixel>) -> Option<Self> {
+        (width > 0 && height > 0 && pixels.len() == width * height).then_some(Texture { width, height, pixels })
+    }
+
+    /// Loads a texture from an image file, such as a PNG or JPEG.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|error| format!("cannot load image {}: {error}", path.display()))?.to_rgba8();
+        let pixels = image.pixels().map(|pixel| Rgba8Pixel::new(pixel[0], pixel[1], pixel[2], pixel[3])).collect();
+        Texture::new(image.width() as usize, image.height() as usize, pixels)
+            .ok_or_else(|| format!("{} is empty", path.display()))
+    }
+
+    /// Returns the rows of pixels.
+    pub fn pixels(&self) -> &[Rgba8Pixel] {
+        &self.pixels
+    }
+
+    /// Samples the texture at u, v in [0, 1], from the top left corner, blending the four
+    /// closest pixels. Positions outside are clamped to the edge.
+    pub fn sample(&self, u: f64, v: f64) -> Rgba8Pixel {
+        let x = (u.clamp(0.0, 1.0) * self.width as f64 - 0.5).max(0.0);
+        let y = (v.clamp(0.0, 1.0) * self.height as f64 - 0.5).max(0.0);
+        let (x0, y0) = ((x as usize).min(self.width - 1), (y as usize).min(self.height - 1));
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+        let pixel = |x: usize, y: usize| self.pixels[y * self.width + x];
+        let blend = |channel: fn(Rgba8Pixel) -> u8| {
+            let top = channel(pixel(x0, y0)) as f64 * (1.0 - fx) + channel(pixel(x1, y0)) as f64 * fx;
+            let bottom = channel(pixel(x0, y1)) as f64 * (1.0 - fx) + channel(pixel(x1, y1)) as f64 * fx;
+            (top * (1.0 - fy) + bottom * fy).round() as u8
+        };
+        Rgba8Pixel::new(blend(|p| p.r), blend(|p| p.g), blend(|p| p.b), blend(|p| p.a))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bilinear_sample() {
+        let black = Rgba8Pixel::new(0, 0, 0, 255);
+        let white = Rgba8Pixel::new(255, 255, 255, 255);
+        let texture = Texture::new(2, 2, vec![black, white, white, black]).unwrap();
+
+        assert_eq!(black, texture.sample(0.0, 0.0));
+        assert_eq!(white, texture.sample(1.0, 0.0));
+        assert_eq!(Rgba8Pixel::new(128, 128, 128, 255), texture.sample(0.5, 0.5));
+        assert_eq!(Rgba8Pixel::new(64, 64, 64, 255), texture.sample(0.375, 0.25));
+        assert_eq!(None, Texture::new(2, 2, vec![black]));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::complex::Complex;
+
+/// Shape that an orbit trap measures the distance of z to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TrapShape {
+    #[default]
+    Point,
+
+    /// The line through the trap's center, along its rotation.
+    Line,
+
+    /// Two perpendicular lines through the center.
+    Cross,
+
+    /// A circle with a radius of the trap's size.
+    Circle,
+
+    /// The Gaussian integers, a grid of points spaced by the trap's size.
+    Lattice,
+
+    /// The square covered by an image texture, from -size to size around the center.
+    ///
+    /// Orbits are trapped by the first point that lands in the square, which is where
+    /// the texture is sampled.
+    Image,
+}
+
+/// An orbit trap, which colors points by how closely their orbit approaches a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitTrap {
+    pub shape: TrapShape,
+    pub center: Complex<f64>,
+
+    /// Scale of the shape in the coordinates of the fractal.
+    pub size: f64,
+
+    /// Counter clockwise rotation of the shape, in radians.
+    pub rotation: f64,
+}
+
+impl OrbitTrap {
+    pub fn new(shape: TrapShape, center: Complex<f64>, size: f64, rotation: f64) -> Self {
+        OrbitTrap { shape, center, size, rotation }
+    }
+
+    /// Maps z to the coordinates of the trap, where the shape has its center at 0, is not
+    /// rotated and has a size of 1.
+    pub fn local(&self, z: Complex<f64>) -> Complex<f64> {
+        (z - self.center) * Complex::from_polar(1.0 / self.size, -self.rotation)
+    }
+
+    /// Returns the distance from z to the shape, in units of the trap's size.
+    pub fn distance(&self, z: Complex<f64>) -> f64 {
+        let w = self.local(z);
+        match self.shape {
+            TrapShape::Point => w.norm(),
+            TrapShape::Line => w.im.abs(),
+            TrapShape::Cross => w.re.abs().min(w.im.abs()),
+            TrapShape::Circle => (w.norm() - 1.0).abs(),
+            TrapShape::Lattice => (w - Complex::new(w.re.round(), w.im.round())).norm(),
+            TrapShape::Image => (w.re.abs().max(w.im.abs()) - 1.0).max(0.0),
+        }
+    }
+
+    /// Returns the distance from z to the outline drawn while editing the trap, in
+    /// units of the trap's size. This is the shape itself, or the edge of an image's square.
+    pub fn outline_distance(&self, z: Complex<f64>) -> f64 {
+        match self.shape {
+            TrapShape::Image => {
+                let w = self.local(z);
+                (w.re.abs().max(w.im.abs()) - 1.0).abs()
+            },
+            _ => self.distance(z),
+        }
+    }
+}
+
+/// Tracks the closest approach of an orbit to a trap as a kernel iterates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrapTracker {
+    trap: Option<OrbitTrap>,
+
+    /// Closest distance so far, in units of the trap's size.
+    pub distance: f64,
+
+    /// Point of the orbit at the closest distance, in the coordinates of the trap.
+    pub point: Complex<f64>,
+}
+
+impl TrapTracker {
+    pub fn new(trap: Option<OrbitTrap>) -> Self {
+        TrapTracker { trap, distance: f64::INFINITY, point: Complex::zero() }
+    }
+
+    /// Checks one point of the orbit, keeping the first of equally close points.
+    pub fn add(&mut self, z: Complex<f64>) {
+        if let Some(trap) = &self.trap {
+            let distance = trap.distance(z);
+            if distance < self.distance {
+                self.distance = distance;
+                self.point = trap.local(z);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes() {
+        let trap = |shape| OrbitTrap::new(shape, Complex::new(1.0, 0.0), 0.5, std::f64::consts::FRAC_PI_2);
+        let z = Complex::new(1.25, 1.0);
+
+        assert!((trap(TrapShape::Point).distance(z) - 2.0615528128088303).abs() < 1e-12);
+        assert!((trap(TrapShape::Line).distance(z) - 0.5).abs() < 1e-12);
+        assert!((trap(TrapShape::Cross).distance(z) - 0.5).abs() < 1e-12);
+        assert!((trap(TrapShape::Circle).distance(z) - 1.0615528128088303).abs() < 1e-12);
+        assert!((trap(TrapShape::Lattice).distance(z) - 0.5).abs() < 1e-12);
+        assert!((trap(TrapShape::Image).distance(z) - 1.0).abs() < 1e-12);
+        assert_eq!(0.0, trap(TrapShape::Image).distance(Complex::new(1.1, 0.1)));
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = TrapTracker::new(Some(OrbitTrap::new(TrapShape::Point, Complex::zero(), 2.0, 0.0)));
+        for z in [Complex::new(4.0, 0.0), Complex::new(0.0, 1.0), Complex::new(0.0, -1.0)] {
+            tracker.add(z);
+        }
+
+        assert_eq!(0.5, tracker.distance);
+        assert_eq!(Complex::new(0.0, 0.5), tracker.point);
+    }
+}
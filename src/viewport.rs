@@ -1,8 +1,6 @@
 use std::marker::PhantomData;
 
-use slint::Rgba8Pixel;
-
-use crate::{fractals::Pixelator, CoordinateSpace, Point};
+use crate::{fractals::{CellPixelator, Pixelator}, CoordinateSpace, Point};
 
 /// A typesafe ViewPort structure that improves upon the slint view point definition.
 #[derive(Debug, Clone, PartialEq)]
@@ -16,16 +14,64 @@ pub struct Viewport<T: CoordinateSpace> {
 
 impl<S: CoordinateSpace> Viewport<S> {
     pub fn new(x1: f64, y1: f64, dx: f64, dy: f64) -> Self {
-        Viewport { x1: x1, y1: y1, dx: dx, dy: dy, _marker: PhantomData }
+        Viewport { x1, y1, dx, dy, _marker: PhantomData }
     }
 
     /// Transforms a given pixelator in the orig_vp<S> to the new_vp<T>.
-    pub fn decorate_pixelator<'a, T: CoordinateSpace>(&'a self, new_vp: &'a Viewport<T>, pixelator: &'a dyn Pixelator<T>) -> impl Pixelator<S> + 'a {
-        let transformer = Box::new(self.transformer(&new_vp));
-        ViewPortDecorator::<'a, S, T> { transformer, pixelator }
+    pub fn decorate_pixelator<'a, T: CoordinateSpace, P>(&'a self, new_vp: &'a Viewport<T>, pixelator: &'a dyn Pixelator<T, P>) -> impl Pixelator<S, P> + 'a {
+        let transformer = Box::new(self.transformer(new_vp));
+        let inverse = Box::new(self.inverse_transformer(new_vp));
+        ViewPortDecorator::<'a, S, T, P> { transformer, inverse, pixelator }
+    }
+
+    /// Transforms a given cell pixelator in the orig_vp<S> to the new_vp<T>.
+    ///
+    /// The cell pixelator is given the whole cell of T covered by each unit square of S.
+    pub fn decorate_cell_pixelator<'a, T: CoordinateSpace, P>(&'a self, new_vp: &'a Viewport<T>, pixelator: &'a dyn CellPixelator<T, P>) -> impl Pixelator<S, P> + 'a {
+        let transformer = Box::new(self.cell_transformer(new_vp));
+        CellViewPortDecorator::<'a, S, T, P> { transformer, pixelator }
+    }
+
+    /// Returns a function mapping the unit square with top left corner at a point of S
+    /// to the cell it covers in new_vp<T>.
+    pub fn cell_transformer<'a, T: CoordinateSpace>(&self, new_vp: &Viewport<T>) -> impl Fn(&Point<S>) -> Cell<T> + Send + Sync + 'a {
+        let transformer = self.transformer(new_vp);
+
+        move | point: &Point<S> | {
+            let corner1 = transformer(point);
+            let corner2 = transformer(&Point::new(point.x + 1.0, point.y + 1.0));
+            Cell {
+                min: Point::new(corner1.x.min(corner2.x), corner1.y.min(corner2.y)),
+                max: Point::new(corner1.x.max(corner2.x), corner1.y.max(corner2.y)),
+            }
+        }
+    }
+
+    pub fn transformer<'a, T: CoordinateSpace>(&self, new_vp: &Viewport<T>) -> impl Fn(&Point<S>) -> Point<T> + Send + Sync + 'a {
+        let (x_factor, y_factor, center_orig, center_new) = self.scaling(new_vp);
+
+        move | point: &Point<S> | {
+            let new_x = (point.x - center_orig.0) * x_factor + center_new.0;
+            let new_y = (point.y - center_orig.1) * y_factor + center_new.1;
+            Point::<T>::new(new_x, new_y)
+        }
     }
 
-    pub fn transformer<'a, T: CoordinateSpace>(&self, new_vp: &Viewport<T>) -> impl Fn(&Point<S>) -> Point<T> + 'a {
+    /// Returns the inverse of `transformer`, which `new_vp.transformer(self)` only is when
+    /// both viewports have the same aspect ratio.
+    pub fn inverse_transformer<'a, T: CoordinateSpace>(&self, new_vp: &Viewport<T>) -> impl Fn(&Point<T>) -> Point<S> + Send + Sync + 'a {
+        let (x_factor, y_factor, center_orig, center_new) = self.scaling(new_vp);
+
+        move | point: &Point<T> | {
+            let orig_x = (point.x - center_new.0) / x_factor + center_orig.0;
+            let orig_y = (point.y - center_new.1) / y_factor + center_orig.1;
+            Point::<S>::new(orig_x, orig_y)
+        }
+    }
+
+    // Factors and centers of the transformation to new_vp<T>, which keeps the centers
+    // aligned and fits new_vp within this viewport.
+    fn scaling<T: CoordinateSpace>(&self, new_vp: &Viewport<T>) -> (f64, f64, (f64, f64), (f64, f64)) {
         let dx_factor = new_vp.dx/self.dx;
         let dy_factor = new_vp.dy/self.dy;
         let factor = dx_factor.abs().max(dy_factor.abs());
@@ -34,32 +80,67 @@ impl<S: CoordinateSpace> Viewport<S> {
         let x_factor = if dx_factor < 0.0 { -factor } else { factor };
         let y_factor = if dy_factor < 0.0 { -factor } else { factor };
 
-        let center_orig_x = self.x1 + self.dx/2.0;
-        let center_orig_y = self.y1 + self.dy/2.0;
-
-        let center_new_x = new_vp.x1 + new_vp.dx/2.0;
-        let center_new_y = new_vp.y1 + new_vp.dy/2.0;
-
-        move | point: &Point<S> | {
-            let new_x = (point.x - center_orig_x) * x_factor + center_new_x;
-            let new_y = (point.y - center_orig_y) * y_factor + center_new_y;
-            Point::<T>::new(new_x, new_y)
-        }
+        let center_orig = (self.x1 + self.dx/2.0, self.y1 + self.dy/2.0);
+        let center_new = (new_vp.x1 + new_vp.dx/2.0, new_vp.y1 + new_vp.dy/2.0);
+        (x_factor, y_factor, center_orig, center_new)
     }
 }
 
+/// The axis aligned rectangle of coordinate space T covered by one unit square
+/// (e.g. one screen pixel) of another coordinate space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell<T: CoordinateSpace> {
+    pub min: Point<T>,
+    pub max: Point<T>,
+}
+
+// Boxed transformation from coordinate space S to T.
+type Transformer<S, T> = Box<dyn Fn(&Point<S>) -> Point<T> + Send + Sync>;
+
+// Boxed transformation from coordinate space S to the cells of T.
+type CellTransformer<S, T> = Box<dyn Fn(&Point<S>) -> Cell<T> + Send + Sync>;
+
 // Internal definition of the view port decorator.
-struct ViewPortDecorator<'a, S: CoordinateSpace, T: CoordinateSpace> {
-    transformer: Box<dyn Fn(&Point<S>) -> Point<T>>,
-    pixelator: &'a dyn Pixelator<T>,
+struct ViewPortDecorator<'a, S: CoordinateSpace, T: CoordinateSpace, P> {
+    transformer: Transformer<S, T>,
+    inverse: Transformer<T, S>,
+    pixelator: &'a dyn Pixelator<T, P>,
 }
 
 // Implementations the translation of the pixelator based on view ports.
-impl<'a, S: CoordinateSpace, T: CoordinateSpace> Pixelator<S> for ViewPortDecorator<'a, S, T> {
-    fn get_pixel(&self, point: &Point<S>) -> Rgba8Pixel {
+impl<'a, S: CoordinateSpace, T: CoordinateSpace, P> Pixelator<S, P> for ViewPortDecorator<'a, S, T, P> {
+    fn get_pixel(&self, point: &Point<S>) -> P {
         let transformed_point = (self.transformer)(point);
         self.pixelator.get_pixel(&transformed_point)
     }
+
+    fn get_pixels(&self, points: &[Point<S>]) -> Vec<P> {
+        let transformed_points: Vec<Point<T>> = points.iter().map(&self.transformer).collect();
+        self.pixelator.get_pixels(&transformed_points)
+    }
+
+    /// Mirrors the point in T, mapping the image back to S.
+    fn mirror_point(&self, point: &Point<S>) -> Option<Point<S>> {
+        let mirrored = self.pixelator.mirror_point(&(self.transformer)(point))?;
+        Some((self.inverse)(&mirrored))
+    }
+
+    fn mirror_pixel(&self, pixel: P) -> P {
+        self.pixelator.mirror_pixel(pixel)
+    }
+}
+
+// Internal definition of the view port decorator for cell pixelators.
+struct CellViewPortDecorator<'a, S: CoordinateSpace, T: CoordinateSpace, P> {
+    transformer: CellTransformer<S, T>,
+    pixelator: &'a dyn CellPixelator<T, P>,
+}
+
+impl<'a, S: CoordinateSpace, T: CoordinateSpace, P> Pixelator<S, P> for CellViewPortDecorator<'a, S, T, P> {
+    fn get_pixel(&self, point: &Point<S>) -> P {
+        let cell = (self.transformer)(point);
+        self.pixelator.get_cell_pixel(&cell)
+    }
 }
 
 
@@ -196,4 +277,29 @@ mod tests {
         tt.test_direct((5.0, 5.0), (100.0, 50.0));
     }
 
+    #[test]
+    fn test_inverse_transformer() {
+        // Viewports of different aspects, whose transformers aren't each other's inverse.
+        let orig_vp = Viewport::<Scope1>::new(0.0, 0.0, 200.0, 100.0);
+        let new_vp = Viewport::<Scope2>::new(0.0, 0.0, 10.0, 10.0);
+        let f = orig_vp.transformer(&new_vp);
+        let f_inv = orig_vp.inverse_transformer(&new_vp);
+
+        assert_eq!(Point::new(100.0, 200.0), f_inv(&Point::new(5.0, 20.0)));
+        assert_eq!(Point::new(0.0, 0.0), f_inv(&Point::new(-5.0, 0.0)));
+        assert_eq!(Point::new(30.0, 70.0), f_inv(&f(&Point::new(30.0, 70.0))));
+        assert_ne!(Point::new(100.0, 200.0), new_vp.transformer(&orig_vp)(&Point::new(5.0, 20.0)));
+    }
+
+    #[test]
+    fn test_cell_transformer() {
+        let orig_vp = Viewport::<Scope1>::new(0.0, 0.0, 800.0, 800.0);
+        let new_vp = Viewport::<Scope2>::new(-2.0, 1.25, 2.5, -2.5);
+        let f = orig_vp.cell_transformer(&new_vp);
+
+        let cell = f(&Point::new(400.0, 400.0));
+        assert_eq!(Point::new(-0.75, -0.003125), cell.min);
+        assert_eq!(Point::new(-0.746875, 0.0), cell.max);
+    }
+
 }
\ No newline at end of file